@@ -0,0 +1,270 @@
+use crate::comment::CommentSyntax;
+use crate::parser::{parse_file, ParsedFile};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// Bump when the parser or the cached model changes, so stale entries are ignored
+const CACHE_VERSION: &str = "6";
+
+/// On-disk parse cache in `.vyasa/cache/`
+///
+/// Each file's `ParsedFile` is stored as `<key>.json`, where the key is the
+/// SHA-256 of the file content together with the comment syntax and cache
+/// version. `index.json` remembers the content hash of every file by path,
+/// size and modification time, so unchanged files are not even read.
+pub struct ParseCache {
+    dir: PathBuf,
+    index: HashMap<String, IndexEntry>,
+    /// Index entries for files seen in this run
+    seen: Mutex<HashMap<String, IndexEntry>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct IndexEntry {
+    size: u64,
+    /// Modification time in nanoseconds since the unix epoch
+    modified: u128,
+    /// SHA-256 of the file content
+    hash: String,
+}
+
+impl ParseCache {
+    /// Open the cache of the shastra at `repo_root` - None if it has no `.vyasa` folder
+    pub fn open(repo_root: &Path) -> Option<Self> {
+        let vyasa_dir = repo_root.join(".vyasa");
+        if !vyasa_dir.is_dir() {
+            return None;
+        }
+        let dir = vyasa_dir.join("cache");
+        let index = fs::read_to_string(dir.join("index.json"))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Some(ParseCache {
+            dir,
+            index,
+            seen: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Return the cached parse of a file, parsing (and caching) it if the
+    /// file changed - None if the file can't be read
+    pub fn get_or_parse(&self, file_path: &Path, file_name: &str, syntax: &CommentSyntax) -> Option<ParsedFile> {
+        let metadata = fs::metadata(file_path).ok()?;
+        let size = metadata.len();
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_nanos());
+
+        // unchanged size and modification time - trust the recorded hash
+        if let Some(entry) = self.index.get(file_name) {
+            if entry.size == size && entry.modified == modified {
+                if let Some(parsed) = self.load(&entry_key(&entry.hash, syntax), file_name) {
+                    self.remember(file_name, entry.clone());
+                    return Some(parsed);
+                }
+            }
+        }
+
+        let content = fs::read_to_string(file_path).ok()?;
+        let hash = hex(&Sha256::digest(content.as_bytes()));
+        let key = entry_key(&hash, syntax);
+        self.remember(file_name, IndexEntry { size, modified, hash });
+
+        if let Some(parsed) = self.load(&key, file_name) {
+            return Some(parsed);
+        }
+
+        let parsed = parse_file(&content, file_name, syntax);
+        if fs::create_dir_all(&self.dir).is_ok() {
+            if let Ok(json) = serde_json::to_string(&parsed) {
+                let _ = fs::write(self.dir.join(format!("{}.json", key)), json);
+            }
+        }
+        Some(parsed)
+    }
+
+    /// Write the index of this run. With `prune`, entries for files not seen in
+    /// this run are dropped - only safe after scanning the whole shastra
+    pub fn save(self, prune: bool) {
+        let seen = self.seen.into_inner().unwrap_or_default();
+        let mut index = if prune { HashMap::new() } else { self.index };
+        index.extend(seen);
+
+        if index.is_empty() || fs::create_dir_all(&self.dir).is_err() {
+            return;
+        }
+        if let Ok(json) = serde_json::to_string(&index) {
+            let _ = fs::write(self.dir.join("index.json"), json);
+        }
+
+        if prune {
+            // remove entries no indexed file refers to (by any comment syntax,
+            // so only entries whose content hash is gone are removed)
+            let live: HashSet<&str> = index.values().map(|e| e.hash.as_str()).collect();
+            let Ok(entries) = fs::read_dir(&self.dir) else {
+                return;
+            };
+            for entry in entries.filter_map(|e| e.ok()) {
+                let path = entry.path();
+                let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+                let Some((hash, _)) = stem.split_once('-') else {
+                    continue;
+                };
+                if !live.contains(hash) {
+                    let _ = fs::remove_file(&path);
+                }
+            }
+        }
+    }
+
+    fn load(&self, key: &str, file_name: &str) -> Option<ParsedFile> {
+        let content = fs::read_to_string(self.dir.join(format!("{}.json", key))).ok()?;
+        let mut parsed: ParsedFile = serde_json::from_str(&content).ok()?;
+        // entries are keyed by content, so a copy of the file may have written it
+        parsed.set_file_name(file_name);
+        Some(parsed)
+    }
+
+    fn remember(&self, file_name: &str, entry: IndexEntry) {
+        if let Ok(mut seen) = self.seen.lock() {
+            seen.insert(file_name.to_string(), entry);
+        }
+    }
+}
+
+/// Cache entry name: `<content hash>-<hash of comment syntax and cache version>`
+fn entry_key(content_hash: &str, syntax: &CommentSyntax) -> String {
+    versioned_key(content_hash, syntax, CACHE_VERSION)
+}
+
+fn versioned_key(content_hash: &str, syntax: &CommentSyntax, version: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(version);
+    hasher.update(serde_json::to_string(syntax).unwrap_or_default());
+    let variant = hex(&hasher.finalize());
+    format!("{}-{}", content_hash, &variant[..16])
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::edit::mula_marker;
+    use crate::testing::TempShastra;
+
+    /// A shastra with one mula bhasya in `notes.md`, its cache, and what
+    /// the cache names the file
+    fn cached(name: &str) -> (TempShastra, ParseCache, String) {
+        let shastra = TempShastra::new(name, &[("notes.md", &format!("> {} - text.\n", mula_marker("m")))]);
+        let cache = ParseCache::open(&shastra.root).unwrap();
+        let file = shastra.path("notes.md").to_string_lossy().to_string();
+        (shastra, cache, file)
+    }
+
+    fn syntax(file: &str) -> CommentSyntax {
+        Config::default().comment_syntax(Path::new(file))
+    }
+
+    /// Cache entries on disk, without the index
+    fn entries(shastra: &TempShastra) -> Vec<PathBuf> {
+        let mut entries: Vec<PathBuf> = fs::read_dir(shastra.path(".vyasa/cache"))
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| !p.ends_with("index.json"))
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    /// Replace every cache entry with an empty parse, so a hit is told from a parse
+    fn tamper(shastra: &TempShastra) {
+        for entry in entries(shastra) {
+            fs::write(entry, serde_json::to_string(&ParsedFile::default()).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn a_miss_parses_and_stores_the_file() {
+        let (shastra, cache, file) = cached("cache-miss");
+        let parsed = cache.get_or_parse(&shastra.path("notes.md"), &file, &syntax(&file)).unwrap();
+        assert_eq!(parsed.bhasyas.len(), 1);
+        assert_eq!(entries(&shastra).len(), 1);
+        assert!(cache.get_or_parse(&shastra.path("missing.md"), "missing.md", &syntax(&file)).is_none());
+    }
+
+    #[test]
+    fn a_hit_loads_the_stored_parse_under_the_file_name() {
+        let (shastra, cache, file) = cached("cache-hit");
+        cache.get_or_parse(&shastra.path("notes.md"), &file, &syntax(&file)).unwrap();
+        cache.save(true);
+        tamper(&shastra);
+
+        let cache = ParseCache::open(&shastra.root).unwrap();
+        let parsed = cache.get_or_parse(&shastra.path("notes.md"), &file, &syntax(&file)).unwrap();
+        assert!(parsed.bhasyas.is_empty());
+    }
+
+    #[test]
+    fn changed_content_is_parsed_again() {
+        let (shastra, cache, file) = cached("cache-content");
+        cache.get_or_parse(&shastra.path("notes.md"), &file, &syntax(&file)).unwrap();
+        cache.save(true);
+        tamper(&shastra);
+
+        shastra.write("notes.md", &format!("> {} - more text.\n", mula_marker("m")));
+        let cache = ParseCache::open(&shastra.root).unwrap();
+        let parsed = cache.get_or_parse(&shastra.path("notes.md"), &file, &syntax(&file)).unwrap();
+        assert_eq!(parsed.bhasyas.len(), 1);
+    }
+
+    #[test]
+    fn comment_syntax_and_cache_version_key_the_entry() {
+        let (shastra, cache, file) = cached("cache-syntax");
+        cache.get_or_parse(&shastra.path("notes.md"), &file, &syntax(&file)).unwrap();
+        tamper(&shastra);
+
+        // the same content read as another kind of file is its own entry
+        cache.get_or_parse(&shastra.path("notes.md"), &file, &syntax("notes.rs")).unwrap();
+        assert_eq!(entries(&shastra).len(), 2);
+
+        let markdown = syntax("notes.md");
+        assert_ne!(versioned_key("abc", &markdown, "1"), versioned_key("abc", &markdown, "2"));
+        assert_eq!(entry_key("abc", &markdown), versioned_key("abc", &markdown, CACHE_VERSION));
+    }
+
+    #[test]
+    fn pruning_drops_entries_of_files_that_are_gone() {
+        let (shastra, cache, file) = cached("cache-prune");
+        shastra.write("other.md", "other text\n");
+        let other = shastra.path("other.md").to_string_lossy().to_string();
+        cache.get_or_parse(&shastra.path("notes.md"), &file, &syntax(&file)).unwrap();
+        cache.get_or_parse(&shastra.path("other.md"), &other, &syntax(&other)).unwrap();
+        cache.save(true);
+        assert_eq!(entries(&shastra).len(), 2);
+
+        // a partial scan keeps what it didn't see
+        let cache = ParseCache::open(&shastra.root).unwrap();
+        cache.get_or_parse(&shastra.path("notes.md"), &file, &syntax(&file)).unwrap();
+        cache.save(false);
+        assert_eq!(entries(&shastra).len(), 2);
+
+        let cache = ParseCache::open(&shastra.root).unwrap();
+        cache.get_or_parse(&shastra.path("notes.md"), &file, &syntax(&file)).unwrap();
+        cache.save(true);
+        assert_eq!(entries(&shastra).len(), 1);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::parser::{Bhasya, BhasyaKind, Repository};
+use std::path::Path;
+
+/// Bhasyas that belong in the canon, in scan order: every live mula bhasya
+/// and, with `include_uddhrit`, every quoted one
+pub fn entries(repo: &Repository, include_uddhrit: bool) -> Vec<&Bhasya> {
+    repo.bhasyas
+        .iter()
+        .filter(|b| match b.kind {
+            BhasyaKind::Mula => true,
+            BhasyaKind::Uddhrit(_) => include_uddhrit,
+            _ => false,
+        })
+        .collect()
+}
+
+/// Render the canon digest: the `entries` grouped under their file path
+///
+/// Paths are relative to the repository root and files keep scan order, so
+/// the same shastra always renders to the same text.
+pub fn render(repo: &Repository, include_uddhrit: bool) -> String {
+    let mut out = String::from("# Canon\n");
+    let mut current_file: Option<&str> = None;
+
+    for bhasya in entries(repo, include_uddhrit) {
+        if current_file != Some(bhasya.file.as_str()) {
+            current_file = Some(bhasya.file.as_str());
+            out.push_str(&format!("\n{}\n", relative_path(repo, &bhasya.file)));
+        }
+
+        out.push('\n');
+        if let BhasyaKind::Uddhrit(shastra) = &bhasya.kind {
+            out.push_str(&format!("shastra: {}\n", shastra));
+        }
+        for line in bhasya.paragraph.lines() {
+            if line.is_empty() {
+                out.push_str(">\n");
+            } else {
+                out.push_str(&format!("> {}\n", line));
+            }
+        }
+    }
+
+    out
+}
+
+/// A scanned file's path relative to the repository root, with `/` separators
+fn relative_path(repo: &Repository, file: &str) -> String {
+    let path = Path::new(file);
+    let relative = repo
+        .root
+        .as_deref()
+        .and_then(|root| path.strip_prefix(root).ok())
+        .unwrap_or(path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::mula_marker;
+    use crate::parser::parse_file;
+    use crate::testing::TempShastra;
+
+    #[test]
+    fn files_keep_scan_order_and_paths_are_relative() {
+        let bhasya = |mantra: &str| format!("> {} - text.\n", mula_marker(mantra));
+        let shastra = TempShastra::new(
+            "canon-order",
+            &[("b.md", &bhasya("b")), ("a/z.md", &bhasya("z")), ("a/y.rs", &format!("// {}", bhasya("y")))],
+        );
+        let repo = shastra.parse();
+        let digest = render(&repo, false);
+        let files: Vec<&str> = digest.lines().filter(|l| l.ends_with(".md") || l.ends_with(".rs")).collect();
+        assert_eq!(files, vec!["a/y.rs", "a/z.md", "b.md"]);
+        assert_eq!(render(&shastra.parse(), false), digest);
+    }
+
+    #[test]
+    fn mantra_text_survives_the_digest() {
+        let notes = format!(
+            "> {} - angle <brackets>, *stars* and `code`.\n>\n> second paragraph.\n",
+            mula_marker("धर्म & <karma> *is* _so_")
+        );
+        let shastra = TempShastra::new("canon-escaping", &[("notes.md", &notes)]);
+        let repo = shastra.parse();
+        let digest = render(&repo, false);
+        assert!(digest.contains(">\n> second paragraph.\n"));
+
+        // read back, the digest defines the same mantras
+        let syntax = repo.config.comment_syntax(Path::new("canon.md"));
+        let parsed = parse_file(&digest, "canon.md", &syntax);
+        let mantras: Vec<&str> =
+            parsed.bhasyas.iter().flat_map(|b| &b.mulas).map(|m| m.mantra_text.as_str()).collect();
+        assert_eq!(mantras, vec!["धर्म & <karma> *is* _so_"]);
+    }
+}
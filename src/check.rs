@@ -1,424 +1,304 @@
-use crate::parser::{Repository, BhasyaKind};
+use crate::edit::anusrit_marker;
+use crate::parser::{spelling_issues, Bhasya, BhasyaKind, Repository, Span};
+use crate::shastra::{ResolveError, ShastraResolver};
+use serde::Serialize;
 use std::collections::HashMap;
-use std::path::Path;
 
-// _| vyasa exits with non zero exit code if any rule is violated |_
-pub fn run(path: &Path) -> Result<(), String> {
-    let repo = Repository::parse(path)?;
+/// The rule a diagnostic reports a violation of, in the order `vyasa check`
+/// prints them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rule {
+    /// A `.vyasa` config file is not valid JSON or does not fit the schema
+    InvalidConfig,
+    /// `.vyasa/config.json` has no `name`
+    MissingName,
+    /// A mula mantra with no mula bhasya explaining it
+    UnexplainedMantra,
+    /// The same mula bhasya written more than once
+    DuplicateBhasya,
+    /// An anusrit to a mantra defined nowhere
+    UndefinedAnusrit,
+    /// An anusrit to a mantra defined in several observed shastras
+    AmbiguousAnusrit,
+    /// A `tyakta: -> _| replacement |_` naming a mantra that is not live
+    TyaktaReplacement,
+    /// A mantra that only matches after normalization
+    NonCanonicalSpelling,
+    /// An `_| mantra |_@shastra` that does not resolve
+    ShastraAnusrit,
+    /// A `shastra:` quote of a bhasya that does not exist (or is tyakta)
+    ShastraQuote,
+    /// A `khandita:` refutation of a bhasya that does not exist
+    Khandita,
+    /// The same bhasya both quoted and refuted
+    KhanditaUddhritConflict,
+    /// Observed shastras disagree on a bhasya and we have taken no position
+    UnresolvedShastraConflict,
+}
 
-    // require shastra name in .vyasa/config.json
-    if repo.config.name.is_none() {
-        return Err("missing 'name' in .vyasa/config.json - every shastra must have a name".to_string());
+impl Rule {
+    /// Heading for a group of violations - "found 3 {heading}:"
+    pub fn heading(&self) -> &'static str {
+        match self {
+            Rule::InvalidConfig => "config file errors",
+            Rule::MissingName => "configuration errors",
+            Rule::UnexplainedMantra => "mantras without explanations",
+            Rule::DuplicateBhasya => "duplicate bhasyas",
+            Rule::UndefinedAnusrit => "undefined anusrits",
+            Rule::AmbiguousAnusrit => "ambiguous anusrits",
+            Rule::TyaktaReplacement => "invalid tyakta replacements",
+            Rule::NonCanonicalSpelling => "non-canonical mantra spellings",
+            Rule::ShastraAnusrit => "shastra anusrit errors",
+            Rule::ShastraQuote => "shastra quote errors",
+            Rule::Khandita => "khandita errors",
+            Rule::KhanditaUddhritConflict => "khandita/uddhrit conflicts",
+            Rule::UnresolvedShastraConflict => "unresolved shastra conflicts",
+        }
     }
 
-    let unexplained = repo.unexplained_mantras();
-
-    let mut has_errors = false;
-    let mut error_counts = Vec::new();
-
-    // check for unexplained mantras
-    if !unexplained.is_empty() {
-        has_errors = true;
-        println!(
-            "found {} mantras without explanations:\n",
-            unexplained.len()
-        );
-        for (mantra_text, info) in &unexplained {
-            println!("  {}:{}", info.file, info.line);
-            println!("    ^{}^\n", truncate(mantra_text, 60));
+    /// Short name used when counting violations - "3 {summary}"
+    pub fn summary(&self) -> &'static str {
+        match self {
+            Rule::UnexplainedMantra => "unexplained mantras",
+            rule => rule.heading(),
         }
-        error_counts.push(format!("{} unexplained mantras", unexplained.len()));
     }
+}
 
-    // check for duplicate bhasyas (same mantra + commentary)
-    let shastra_name = repo.config.name.as_ref().unwrap();
-    let duplicate_bhasyas = check_duplicate_bhasyas(&repo);
-    if !duplicate_bhasyas.is_empty() {
-        has_errors = true;
-        println!(
-            "found {} duplicate bhasyas:\n",
-            duplicate_bhasyas.len()
-        );
-        for (_mantra, paragraph, locations) in &duplicate_bhasyas {
-            // show the original paragraph with indentation
-            for line in paragraph.lines().take(4) {
-                println!("  {}", line);
-            }
-            if paragraph.lines().count() > 4 {
-                println!("  ...");
-            }
-            println!();
-            println!("  found at:");
-            for (file, line) in locations {
-                println!("    - {}:{}", file, line);
-            }
-            println!("\n  use `shastra: {}` to quote from canonical location\n", shastra_name);
-        }
-        error_counts.push(format!("{} duplicate bhasyas", duplicate_bhasyas.len()));
+/// One rule violation found by `check`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub rule: Rule,
+    /// File the violation is in, if it is in one
+    pub file: Option<String>,
+    /// Where in the file
+    pub span: Option<Span>,
+    /// What is wrong
+    pub message: String,
+    /// Further detail and hints, one line each
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    fn new(rule: Rule, message: String) -> Self {
+        Diagnostic { rule, file: None, span: None, message, notes: Vec::new() }
     }
 
-    // _| vyasa reports undefined anusrits |_
-    let (undefined_refs, ambiguous_refs) = check_undefined_anusrits(&repo);
-    if !undefined_refs.is_empty() {
-        has_errors = true;
-        println!(
-            "found {} undefined anusrits:\n",
-            undefined_refs.len()
-        );
-        for (file, line, text) in &undefined_refs {
-            println!("  {}:{}", file, line);
-            println!("    anusrit: {}\n", truncate(text, 60));
-        }
-        error_counts.push(format!("{} undefined anusrits", undefined_refs.len()));
+    fn at(rule: Rule, file: &str, span: Span, message: String) -> Self {
+        Diagnostic { file: Some(file.to_string()), span: Some(span), ..Diagnostic::new(rule, message) }
     }
 
-    if !ambiguous_refs.is_empty() {
-        has_errors = true;
-        println!(
-            "found {} ambiguous anusrits:\n",
-            ambiguous_refs.len()
-        );
-        for (file, line, text, shastras) in &ambiguous_refs {
-            println!("  {}:{}", file, line);
-            println!("    ^{}^", truncate(text, 60));
-            println!("    found in: {}", shastras.join(", "));
-            println!("    use @shastra to disambiguate\n");
-        }
-        error_counts.push(format!("{} ambiguous anusrits", ambiguous_refs.len()));
+    fn note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
     }
+}
 
-    // check external shastra anusrits
-    let shastra_anusrit_errors = check_shastra_anusrits(&repo);
-    if !shastra_anusrit_errors.is_empty() {
-        has_errors = true;
-        println!("found {} shastra anusrit errors:\n", shastra_anusrit_errors.len());
-        for error in &shastra_anusrit_errors {
-            println!("  {}\n", error);
-        }
-        error_counts.push(format!("{} shastra anusrit errors", shastra_anusrit_errors.len()));
+/// Check a parsed shastra against every rule, returning violations grouped by
+/// rule (in `Rule` order) and in source order within a rule
+pub fn check(repo: &Repository) -> Vec<Diagnostic> {
+    check_with(repo, &mut ShastraResolver::new(repo))
+}
+
+/// `check` resolving observed shastras with `resolver`, so a long-running
+/// caller parses each of them once rather than on every check
+pub fn check_with(repo: &Repository, resolver: &mut ShastraResolver) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for error in &repo.config_errors {
+        diagnostics.push(Diagnostic::at(Rule::InvalidConfig, &error.file, error.span, error.message.clone()));
     }
 
-    // check shastra-quoted bhasyas
-    let shastra_errors = check_shastra_quotes(&repo);
-    if !shastra_errors.is_empty() {
-        has_errors = true;
-        println!("found {} shastra quote errors:\n", shastra_errors.len());
-        for error in &shastra_errors {
-            println!("  {}\n", error);
-        }
-        error_counts.push(format!("{} shastra quote errors", shastra_errors.len()));
+    // require shastra name in .vyasa/config.json
+    if repo.config.name.is_none() {
+        diagnostics.push(Diagnostic {
+            file: Some(".vyasa/config.json".to_string()),
+            ..Diagnostic::new(
+                Rule::MissingName,
+                "missing 'name' - every shastra must have a name".to_string(),
+            )
+        });
     }
 
-    // check khandita (refuted) bhasyas
-    let khandita_errors = check_khandita(&repo);
-    if !khandita_errors.is_empty() {
-        has_errors = true;
-        println!("found {} khandita errors:\n", khandita_errors.len());
-        for error in &khandita_errors {
-            println!("  {}\n", error);
-        }
-        error_counts.push(format!("{} khandita errors", khandita_errors.len()));
+    for (mantra_text, info) in repo.unexplained_mantras() {
+        diagnostics.push(Diagnostic::at(
+            Rule::UnexplainedMantra,
+            &info.file,
+            info.span,
+            format!("^{}^", truncate(mantra_text, 60)),
+        ));
     }
 
-    // check for conflicting khandita/uddhrit (can't both refute and quote same bhasya)
-    let conflict_errors = check_khandita_uddhrit_conflicts(&repo);
-    if !conflict_errors.is_empty() {
-        has_errors = true;
-        println!("found {} khandita/uddhrit conflicts:\n", conflict_errors.len());
-        for error in &conflict_errors {
-            println!("  {}\n", error);
-        }
-        error_counts.push(format!("{} khandita/uddhrit conflicts", conflict_errors.len()));
+    check_duplicate_bhasyas(repo, &mut diagnostics);
+    // _| vyasa reports undefined anusrits |_
+    check_undefined_anusrits(repo, resolver, &mut diagnostics);
+    // _| tyakta replacements must be live |_
+    check_tyakta_replacements(repo, resolver, &mut diagnostics);
+
+    // _| mantra text is normalized |_
+    for spelling in &repo.non_canonical {
+        diagnostics.push(
+            Diagnostic::at(
+                Rule::NonCanonicalSpelling,
+                &spelling.file,
+                spelling.span,
+                format!("^{}^", truncate(&spelling.canonical, 60)),
+            )
+            .note(spelling_issues(&spelling.raw).join(", ")),
+        );
     }
 
-    // check for unresolved conflicts between shastras I follow
-    let unresolved_errors = check_unresolved_shastra_conflicts(&repo);
-    if !unresolved_errors.is_empty() {
-        has_errors = true;
-        println!("found {} unresolved shastra conflicts:\n", unresolved_errors.len());
-        for error in &unresolved_errors {
-            println!("  {}\n", error);
+    check_shastra_anusrits(repo, resolver, &mut diagnostics);
+    check_shastra_quotes(repo, resolver, &mut diagnostics);
+    check_khandita(repo, resolver, &mut diagnostics);
+    check_khandita_uddhrit_conflicts(repo, &mut diagnostics);
+    check_unresolved_shastra_conflicts(repo, resolver, &mut diagnostics);
+
+    // stable sort keeps source order within each rule
+    diagnostics.sort_by_key(|d| d.rule);
+    diagnostics
+}
+
+/// Count violations per rule - "2 unexplained mantras, 1 duplicate bhasyas" -
+/// or None if there are none
+pub fn summarize(diagnostics: &[Diagnostic]) -> Option<String> {
+    let mut counts: Vec<(Rule, usize)> = Vec::new();
+    for diagnostic in diagnostics {
+        match counts.last_mut() {
+            Some((rule, count)) if *rule == diagnostic.rule => *count += 1,
+            _ => counts.push((diagnostic.rule, 1)),
         }
-        error_counts.push(format!("{} unresolved shastra conflicts", unresolved_errors.len()));
     }
-
-    if has_errors {
-        Err(error_counts.join(", "))
-    } else {
-        Ok(())
+    if counts.is_empty() {
+        return None;
     }
+    let counts: Vec<_> = counts
+        .iter()
+        .map(|(rule, count)| format!("{} {}", count, rule.summary()))
+        .collect();
+    Some(counts.join(", "))
 }
 
 /// Check external shastra anusrits: verify alias, path, and mantra exists in mula form
-fn check_shastra_anusrits(repo: &Repository) -> Vec<String> {
-    let mut errors = Vec::new();
-
-    // cache parsed external shastras
-    let mut shastra_repos: HashMap<String, Option<Repository>> = HashMap::new();
-
-    // check each anusrit with a shastra reference
+fn check_shastra_anusrits(repo: &Repository, resolver: &mut ShastraResolver, diagnostics: &mut Vec<Diagnostic>) {
     for anusrit in &repo.anusrits {
-        if let Some(shastra_name) = &anusrit.shastra {
-            // check if alias is defined in shastra.json
-            let Some(shastra_path) = repo.shastra_config.aliases.get(shastra_name) else {
-                errors.push(format!(
-                    "{}:{}: undefined shastra '{}' in anusrit @{}",
-                    anusrit.file,
-                    anusrit.line,
+        let Some(shastra_name) = &anusrit.shastra else {
+            continue;
+        };
+        let message = match resolver.resolve(shastra_name) {
+            // check if the mantra exists in mula form (not tyakta-only)
+            Ok(external) if external.is_live(&anusrit.mantra_text) => continue,
+            Ok(external) => {
+                let message = format!(
+                    "mantra not found in shastra '{}': ^{}^",
                     shastra_name,
-                    shastra_name
-                ));
-                continue;
-            };
-
-            // check if it's a local folder path
-            let is_folder = shastra_path.starts_with('/')
-                || shastra_path.starts_with("./")
-                || shastra_path.starts_with("../");
-
-            if !is_folder {
-                errors.push(format!(
-                    "shastra '{}' refers to '{}' - only local folder paths are currently supported",
-                    shastra_name, shastra_path
-                ));
+                    truncate(&anusrit.mantra_text, 30)
+                );
+                let diagnostic = Diagnostic::at(Rule::ShastraAnusrit, &anusrit.file, anusrit.span, message);
+                diagnostics.push(with_replacement_note(external, Some(shastra_name), &anusrit.mantra_text, diagnostic));
                 continue;
             }
-
-            // check if resolved path exists
-            let path = Path::new(shastra_path);
-            if !path.exists() {
-                errors.push(format!(
-                    "shastra '{}' folder does not exist: {}",
-                    shastra_name, shastra_path
-                ));
-                continue;
-            }
-
-            // load the external shastra repo
-            let external_repo = shastra_repos
-                .entry(shastra_name.clone())
-                .or_insert_with(|| Repository::parse(path).ok());
-
-            if let Some(external) = external_repo {
-                // check if the mantra exists in mula form (not tyakta-only)
-                let mantra_exists = external.mantras.contains_key(&anusrit.mantra_text);
-
-                if !mantra_exists {
-                    errors.push(format!(
-                        "{}:{}: mantra not found in shastra '{}': ^{}^",
-                        anusrit.file,
-                        anusrit.line,
-                        shastra_name,
-                        truncate(&anusrit.mantra_text, 30)
-                    ));
-                }
-            } else {
-                errors.push(format!(
-                    "failed to parse shastra '{}' at {}",
-                    shastra_name, shastra_path
-                ));
+            Err(ResolveError::UndefinedAlias(_)) => {
+                format!("undefined shastra '{}' in anusrit @{}", shastra_name, shastra_name)
             }
-        }
+            Err(e) => e.to_string(),
+        };
+        let diagnostic = Diagnostic::at(Rule::ShastraAnusrit, &anusrit.file, anusrit.span, message);
+        diagnostics.push(with_alias_file_note(repo, shastra_name, diagnostic));
     }
-
-    errors
 }
 
 /// Check shastra-quoted bhasyas: verify they exist in source, error if tyakta
-fn check_shastra_quotes(repo: &Repository) -> Vec<String> {
-    let mut errors = Vec::new();
-
-    // cache parsed external shastras
-    let mut shastra_repos: HashMap<String, Option<Repository>> = HashMap::new();
-
-    let self_name = repo.config.name.as_ref().map(|s| s.as_str());
+fn check_shastra_quotes(repo: &Repository, resolver: &mut ShastraResolver, diagnostics: &mut Vec<Diagnostic>) {
+    let self_name = repo.config.name.as_deref();
 
     // find all mula mantras in bhasyas with Uddhrit kind
     for (mantra_text, bhasya) in repo.mula_mantras_with_bhasyas() {
-        if let BhasyaKind::Uddhrit(ref shastra_name) = bhasya.kind {
-            // check if this is a self-reference
-            if self_name == Some(shastra_name.as_str()) {
-                // self-reference: check against current repo
-                let has_mula = repo.mantras.contains_key(mantra_text);
-                if !has_mula {
-                    // check if it exists as tyakta only
-                    let has_any = repo.has_any_bhasya_for_mantra(mantra_text);
-                    if !has_any {
-                        errors.push(format!(
-                            "{}:{}: mantra not found in self: ^{}^",
-                            bhasya.file,
-                            bhasya.line,
-                            truncate(mantra_text, 30)
-                        ));
-                    }
-                }
-                continue;
-            }
-
-            // resolve shastra name to path via shastra.json
-            let Some(shastra_path) = repo.shastra_config.aliases.get(shastra_name) else {
-                errors.push(format!(
-                    "{}:{}: undefined shastra '{}' for quoted ^{}^",
-                    bhasya.file,
-                    bhasya.line,
-                    shastra_name,
-                    truncate(mantra_text, 30)
-                ));
-                continue;
-            };
-
-            // check if it's a local folder path
-            let is_folder = shastra_path.starts_with('/')
-                || shastra_path.starts_with("./")
-                || shastra_path.starts_with("../");
-
-            if !is_folder {
-                errors.push(format!(
-                    "shastra '{}' refers to '{}' - only local folder paths are currently supported",
-                    shastra_name, shastra_path
-                ));
-                continue;
-            }
-
-            // check if resolved path exists
-            let path = Path::new(shastra_path);
-            if !path.exists() {
-                errors.push(format!(
-                    "shastra '{}' folder does not exist: {}",
-                    shastra_name, shastra_path
+        let BhasyaKind::Uddhrit(ref shastra_name) = bhasya.kind else {
+            continue;
+        };
+
+        // check if this is a self-reference
+        if self_name == Some(shastra_name.as_str()) {
+            // self-reference: check against current repo (tyakta-only is fine)
+            if !repo.mantras.contains_key(mantra_text) && !repo.has_any_bhasya_for_mantra(mantra_text) {
+                diagnostics.push(Diagnostic::at(
+                    Rule::ShastraQuote,
+                    &bhasya.file,
+                    bhasya.span,
+                    format!("mantra not found in self: ^{}^", truncate(mantra_text, 30)),
                 ));
-                continue;
             }
+            continue;
+        }
 
-            // load the external shastra repo
-            let external_repo = shastra_repos
-                .entry(shastra_name.clone())
-                .or_insert_with(|| Repository::parse(path).ok());
-
-            if let Some(external) = external_repo {
-                // check if mantra exists in mula form (non-tyakta bhasya)
-                let has_mula = external.mantras.contains_key(mantra_text);
-                // check if any bhasya (mula or tyakta) exists
-                let has_any_bhasya = external.has_any_bhasya_for_mantra(mantra_text);
-
-                if !has_any_bhasya {
-                    // no bhasya at all - error
-                    errors.push(format!(
-                        "{}:{}: mantra not found in shastra '{}': ^{}^",
-                        bhasya.file,
-                        bhasya.line,
+        let message = match resolver.resolve(shastra_name) {
+            Ok(external) => {
+                if !external.has_any_bhasya_for_mantra(mantra_text) {
+                    // no bhasya at all
+                    format!(
+                        "mantra not found in shastra '{}': ^{}^",
                         shastra_name,
                         truncate(mantra_text, 30)
-                    ));
+                    )
+                } else if !external.is_live(mantra_text) {
+                    // only tyakta bhasya exists
+                    let message = format!("quoted tyakta from '{}': ^{}^", shastra_name, truncate(mantra_text, 30));
+                    let diagnostic = Diagnostic::at(Rule::ShastraQuote, &bhasya.file, bhasya.span, message);
+                    diagnostics.push(with_replacement_note(external, Some(shastra_name), mantra_text, diagnostic));
+                    continue;
+                } else {
                     continue;
                 }
-
-                if !has_mula {
-                    // only tyakta bhasya exists - error
-                    errors.push(format!(
-                        "{}:{}: quoted tyakta from '{}': ^{}^",
-                        bhasya.file,
-                        bhasya.line,
-                        shastra_name,
-                        truncate(mantra_text, 30)
-                    ));
-                }
-            } else {
-                errors.push(format!(
-                    "failed to parse shastra '{}' at {}",
-                    shastra_name, shastra_path
-                ));
             }
-        }
+            Err(ResolveError::UndefinedAlias(_)) => format!(
+                "undefined shastra '{}' for quoted ^{}^",
+                shastra_name,
+                truncate(mantra_text, 30)
+            ),
+            Err(e) => e.to_string(),
+        };
+        let diagnostic = Diagnostic::at(Rule::ShastraQuote, &bhasya.file, bhasya.span, message);
+        diagnostics.push(with_alias_file_note(repo, shastra_name, diagnostic));
     }
-
-    errors
 }
 
 /// Check khandita (refuted) bhasyas: verify they exist in source shastra
-fn check_khandita(repo: &Repository) -> Vec<String> {
-    let mut errors = Vec::new();
-
-    // cache parsed external shastras
-    let mut shastra_repos: HashMap<String, Option<Repository>> = HashMap::new();
-
-    // find all mula mantras in bhasyas with Khandita kind
+fn check_khandita(repo: &Repository, resolver: &mut ShastraResolver, diagnostics: &mut Vec<Diagnostic>) {
     for (mantra_text, bhasya) in repo.mula_mantras_with_bhasyas() {
-        if let BhasyaKind::Khandita(ref shastra_name) = bhasya.kind {
-            // resolve shastra name to path via shastra.json
-            let Some(shastra_path) = repo.shastra_config.aliases.get(shastra_name) else {
-                errors.push(format!(
-                    "{}:{}: undefined shastra '{}' for khandita ^{}^",
-                    bhasya.file,
-                    bhasya.line,
-                    shastra_name,
-                    truncate(mantra_text, 30)
-                ));
-                continue;
-            };
-
-            // check if it's a local folder path
-            let is_folder = shastra_path.starts_with('/')
-                || shastra_path.starts_with("./")
-                || shastra_path.starts_with("../");
-
-            if !is_folder {
-                errors.push(format!(
-                    "shastra '{}' refers to '{}' - only local folder paths are currently supported",
-                    shastra_name, shastra_path
-                ));
-                continue;
-            }
-
-            // check if resolved path exists
-            let path = Path::new(shastra_path);
-            if !path.exists() {
-                errors.push(format!(
-                    "shastra '{}' folder does not exist: {}",
-                    shastra_name, shastra_path
-                ));
-                continue;
-            }
-
-            // load the external shastra repo
-            let external_repo = shastra_repos
-                .entry(shastra_name.clone())
-                .or_insert_with(|| Repository::parse(path).ok());
-
-            if let Some(external) = external_repo {
-                // check if any bhasya exists for this mantra
-                let has_any_bhasya = external.has_any_bhasya_for_mantra(mantra_text);
-
-                if !has_any_bhasya {
-                    // no bhasya at all - error: can't refute what doesn't exist
-                    errors.push(format!(
-                        "{}:{}: cannot khandita non-existent bhasya from '{}': ^{}^",
-                        bhasya.file,
-                        bhasya.line,
-                        shastra_name,
-                        truncate(mantra_text, 30)
-                    ));
-                    continue;
-                }
-                // note: if source already tyakta'd it, that's fine - our khandita may have
-                // contributed to that decision, so we keep it without warning
-            } else {
-                errors.push(format!(
-                    "failed to parse shastra '{}' at {}",
-                    shastra_name, shastra_path
-                ));
-            }
-        }
+        let BhasyaKind::Khandita(ref shastra_name) = bhasya.kind else {
+            continue;
+        };
+        let message = match resolver.resolve(shastra_name) {
+            // note: if source already tyakta'd it, that's fine - our khandita may have
+            // contributed to that decision, so we keep it without warning
+            Ok(external) if external.has_any_bhasya_for_mantra(mantra_text) => continue,
+            // can't refute what doesn't exist
+            Ok(_) => format!(
+                "cannot khandita non-existent bhasya from '{}': ^{}^",
+                shastra_name,
+                truncate(mantra_text, 30)
+            ),
+            Err(ResolveError::UndefinedAlias(_)) => format!(
+                "undefined shastra '{}' for khandita ^{}^",
+                shastra_name,
+                truncate(mantra_text, 30)
+            ),
+            Err(e) => e.to_string(),
+        };
+        let diagnostic = Diagnostic::at(Rule::Khandita, &bhasya.file, bhasya.span, message);
+        diagnostics.push(with_alias_file_note(repo, shastra_name, diagnostic));
     }
-
-    errors
 }
 
 /// Check for unresolved conflicts between shastras I follow
 /// If shastra X khandits a bhasya and shastra Y uddhrits it, I must take a position
-fn check_unresolved_shastra_conflicts(repo: &Repository) -> Vec<String> {
-    let mut errors = Vec::new();
-
+fn check_unresolved_shastra_conflicts(
+    repo: &Repository,
+    resolver: &mut ShastraResolver,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
     // collect my positions: (mantra_text, source_shastra) -> "khandita" | "uddhrit"
     let mut my_positions: HashMap<(String, String), &str> = HashMap::new();
     for (mantra_text, bhasya) in repo.mula_mantras_with_bhasyas() {
@@ -437,81 +317,67 @@ fn check_unresolved_shastra_conflicts(repo: &Repository) -> Vec<String> {
     // key: (mantra_text, source_shastra) -> Vec<(follower_shastra, position)>
     let mut external_positions: HashMap<(String, String), Vec<(String, &str)>> = HashMap::new();
 
-    for (shastra_name, shastra_path) in &repo.shastra_config.aliases {
-        let is_folder = shastra_path.starts_with('/')
-            || shastra_path.starts_with("./")
-            || shastra_path.starts_with("../");
-
-        if !is_folder {
+    for shastra_name in resolver.aliases() {
+        // unresolvable shastras are reported where they are used
+        let Ok(external) = resolver.resolve(&shastra_name) else {
             continue;
-        }
-
-        let path = Path::new(shastra_path);
-        if !path.exists() {
-            continue;
-        }
-
-        if let Ok(external) = Repository::parse(path) {
-            for (ext_mantra_text, ext_bhasya) in external.mula_mantras_with_bhasyas() {
-                match &ext_bhasya.kind {
-                    BhasyaKind::Khandita(source) => {
-                        let key = (ext_mantra_text.to_string(), source.clone());
-                        external_positions
-                            .entry(key)
-                            .or_default()
-                            .push((shastra_name.clone(), "khandita"));
-                    }
-                    BhasyaKind::Uddhrit(source) => {
-                        let key = (ext_mantra_text.to_string(), source.clone());
-                        external_positions
-                            .entry(key)
-                            .or_default()
-                            .push((shastra_name.clone(), "uddhrit"));
-                    }
-                    _ => {}
-                }
-            }
+        };
+        for (ext_mantra_text, ext_bhasya) in external.mula_mantras_with_bhasyas() {
+            let (source, position) = match &ext_bhasya.kind {
+                BhasyaKind::Khandita(source) => (source, "khandita"),
+                BhasyaKind::Uddhrit(source) => (source, "uddhrit"),
+                _ => continue,
+            };
+            external_positions
+                .entry((ext_mantra_text.to_string(), source.clone()))
+                .or_default()
+                .push((shastra_name.clone(), position));
         }
     }
 
     // find conflicts: same (mantra, source) has both khandita and uddhrit
+    let mut external_positions: Vec<_> = external_positions
+        .into_iter()
+        .map(|(key, mut positions)| {
+            positions.sort();
+            (key, positions)
+        })
+        .collect();
+    external_positions.sort();
     for (key, positions) in &external_positions {
         let has_khandita = positions.iter().any(|(_, pos)| *pos == "khandita");
         let has_uddhrit = positions.iter().any(|(_, pos)| *pos == "uddhrit");
 
-        if has_khandita && has_uddhrit {
-            // there's a conflict - check if I've resolved it
-            if !my_positions.contains_key(key) {
-                let khandita_by: Vec<_> = positions
-                    .iter()
-                    .filter(|(_, pos)| *pos == "khandita")
-                    .map(|(s, _)| s.as_str())
-                    .collect();
-                let uddhrit_by: Vec<_> = positions
+        // there's a conflict - check if I've resolved it
+        if has_khandita && has_uddhrit && !my_positions.contains_key(key) {
+            let by = |position: &str| {
+                positions
                     .iter()
-                    .filter(|(_, pos)| *pos == "uddhrit")
+                    .filter(|(_, pos)| *pos == position)
                     .map(|(s, _)| s.as_str())
-                    .collect();
-
-                errors.push(format!(
-                    "unresolved conflict for ^{}^ from '{}': khandita by [{}], uddhrit by [{}] - add your own khandita: or shastra: to resolve",
-                    truncate(&key.0, 30),
-                    key.1,
-                    khandita_by.join(", "),
-                    uddhrit_by.join(", ")
-                ));
-            }
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            diagnostics.push(
+                Diagnostic::new(
+                    Rule::UnresolvedShastraConflict,
+                    format!(
+                        "unresolved conflict for ^{}^ from '{}': khandita by [{}], uddhrit by [{}]",
+                        truncate(&key.0, 30),
+                        key.1,
+                        by("khandita"),
+                        by("uddhrit")
+                    ),
+                )
+                .note("add your own khandita: or shastra: to resolve".to_string()),
+            );
         }
     }
-
-    errors
 }
 
 /// Check that same bhasya is not both khandita and uddhrit from same shastra
 /// If you refute a bhasya, you must refute it consistently - no quoting it elsewhere
-fn check_khandita_uddhrit_conflicts(repo: &Repository) -> Vec<String> {
-    let mut errors = Vec::new();
-
+fn check_khandita_uddhrit_conflicts(repo: &Repository, diagnostics: &mut Vec<Diagnostic>) {
     // collect all khandita: (mantra_text, shastra) -> (file, line)
     let mut khandita_refs: HashMap<(String, String), (String, usize)> = HashMap::new();
     for (mantra_text, bhasya) in repo.mula_mantras_with_bhasyas() {
@@ -528,70 +394,78 @@ fn check_khandita_uddhrit_conflicts(repo: &Repository) -> Vec<String> {
         if let BhasyaKind::Uddhrit(ref shastra) = bhasya.kind {
             let key = (mantra_text.to_string(), shastra.clone());
             if let Some((khandita_file, khandita_line)) = khandita_refs.get(&key) {
-                errors.push(format!(
-                    "{}:{}: cannot uddhrit ^{}^ from '{}' - already khandita at {}:{}",
-                    bhasya.file,
-                    bhasya.line,
-                    truncate(mantra_text, 30),
-                    shastra,
-                    khandita_file,
-                    khandita_line
+                diagnostics.push(Diagnostic::at(
+                    Rule::KhanditaUddhritConflict,
+                    &bhasya.file,
+                    bhasya.span,
+                    format!(
+                        "cannot uddhrit ^{}^ from '{}' - already khandita at {}:{}",
+                        truncate(mantra_text, 30),
+                        shastra,
+                        khandita_file,
+                        khandita_line
+                    ),
                 ));
             }
         }
     }
-
-    errors
 }
 
 /// Check for duplicate bhasyas - same mantra in same paragraph content must be unique
-/// Returns: Vec<(mantra_text, paragraph, locations)>
-fn check_duplicate_bhasyas(repo: &Repository) -> Vec<(String, String, Vec<(String, usize)>)> {
-    // key: (mantra_text, paragraph) -> list of (file, line)
-    let mut occurrences: HashMap<(String, String), Vec<(String, usize, String)>> = HashMap::new();
+fn check_duplicate_bhasyas(repo: &Repository, diagnostics: &mut Vec<Diagnostic>) {
+    let shastra_name = repo.config.name.as_deref().unwrap_or("<name>");
+    for (mantra, copies) in duplicate_bhasyas(repo) {
+        let found_at: Vec<_> = copies
+            .iter()
+            .map(|b| format!("{}:{}-{}", b.file, b.span.start.line, b.span.end.line))
+            .collect();
+        diagnostics.push(
+            Diagnostic::at(
+                Rule::DuplicateBhasya,
+                &copies[0].file,
+                copies[0].span,
+                format!("duplicate bhasya for ^{}^", truncate(mantra, 60)),
+            )
+            .note(format!("found at: {}", found_at.join(", ")))
+            .note(format!("use `shastra: {}` to quote from canonical location", shastra_name)),
+        );
+    }
+}
+
+/// Mula bhasyas written more than once - the same mantra with the same
+/// commentary - as (mantra, copies in source order), sorted by first copy
+pub(crate) fn duplicate_bhasyas(repo: &Repository) -> Vec<(&str, Vec<&Bhasya>)> {
+    // key: (mantra_text, paragraph with each line trimmed)
+    let mut occurrences: HashMap<(&str, String), Vec<&Bhasya>> = HashMap::new();
 
     for (mantra_text, bhasya) in repo.mula_mantras_with_bhasyas() {
         // skip non-Mula bhasyas - duplicates allowed for uddhrit/khandita
         if !matches!(bhasya.kind, BhasyaKind::Mula) {
             continue;
         }
-
-        // normalize paragraph for comparison (trim whitespace)
         let normalized_para = bhasya.paragraph.lines()
             .map(|l| l.trim())
             .collect::<Vec<_>>()
             .join("\n");
-
-        let key = (mantra_text.to_string(), normalized_para);
-        occurrences
-            .entry(key)
-            .or_default()
-            .push((bhasya.file.clone(), bhasya.line, bhasya.paragraph.clone()));
+        let copies = occurrences.entry((mantra_text, normalized_para)).or_default();
+        // a bhasya repeating its own mula line is not a copy of itself
+        if !copies.iter().any(|c| std::ptr::eq(*c, bhasya)) {
+            copies.push(bhasya);
+        }
     }
 
-    // collect only those with more than one occurrence
-    occurrences
+    let mut duplicates: Vec<_> = occurrences
         .into_iter()
-        .filter(|(_, locs)| locs.len() > 1)
-        .map(|((mantra, _commentary), locs)| {
-            // use the paragraph from the first occurrence for display
-            let paragraph = locs.first().map(|(_, _, p)| p.to_string()).unwrap_or_default();
-            (
-                mantra.to_string(),
-                paragraph,
-                locs.into_iter().map(|(f, l, _)| (f.to_string(), l)).collect(),
-            )
-        })
-        .collect()
+        .filter(|(_, copies)| copies.len() > 1)
+        .map(|((mantra, _), copies)| (mantra, copies))
+        .collect();
+    duplicates.sort_by(|a, b| (&a.1[0].file, a.1[0].span, a.0).cmp(&(&b.1[0].file, b.1[0].span, b.0)));
+    duplicates
 }
 
 // _| vyasa reports undefined anusrits |_
-fn check_undefined_anusrits(repo: &Repository) -> (Vec<(String, usize, String)>, Vec<(String, usize, String, Vec<String>)>) {
-    let mut undefined = Vec::new();
-    let mut ambiguous = Vec::new();
-
-    // cache parsed external shastras
-    let mut shastra_repos: HashMap<String, Option<Repository>> = HashMap::new();
+fn check_undefined_anusrits(repo: &Repository, resolver: &mut ShastraResolver, diagnostics: &mut Vec<Diagnostic>) {
+    let aliases = resolver.aliases();
 
     for anusrit in &repo.anusrits {
         // explicit @shastra anusrits are checked separately
@@ -600,69 +474,158 @@ fn check_undefined_anusrits(repo: &Repository) -> (Vec<(String, usize, String)>,
         }
 
         // first check current shastra
-        if repo.mantras.contains_key(&anusrit.mantra_text) {
+        if repo.is_live(&anusrit.mantra_text) {
             continue;
         }
 
-        // not in current shastra - check all external shastras
-        let mut found_in: Vec<String> = Vec::new();
-
-        for (shastra_name, shastra_path) in &repo.shastra_config.aliases {
-            // only check local folder paths
-            let is_folder = shastra_path.starts_with('/')
-                || shastra_path.starts_with("./")
-                || shastra_path.starts_with("../");
-
-            if !is_folder {
-                continue;
-            }
+        // not in current shastra - check all external shastras (sorted)
+        let found_in: Vec<&String> = aliases
+            .iter()
+            .filter(|alias| {
+                resolver
+                    .resolve(alias)
+                    .is_ok_and(|external| external.is_live(&anusrit.mantra_text))
+            })
+            .collect();
 
-            let path = Path::new(shastra_path);
-            if !path.exists() {
-                continue;
-            }
-
-            // load external shastra
-            let external_repo = shastra_repos
-                .entry(shastra_name.clone())
-                .or_insert_with(|| Repository::parse(path).ok());
-
-            if let Some(external) = external_repo {
-                if external.mantras.contains_key(&anusrit.mantra_text) {
-                    found_in.push(shastra_name.clone());
+        if found_in.is_empty() {
+            // not found anywhere - but it may have been abandoned for another
+            let mut diagnostic = Diagnostic::at(
+                Rule::UndefinedAnusrit,
+                &anusrit.file,
+                anusrit.span,
+                format!("anusrit: {}", truncate(&anusrit.mantra_text, 60)),
+            );
+            diagnostic = with_replacement_note(repo, None, &anusrit.mantra_text, diagnostic);
+            for alias in &aliases {
+                if let Ok(external) = resolver.resolve(alias) {
+                    diagnostic = with_replacement_note(external, Some(alias), &anusrit.mantra_text, diagnostic);
                 }
             }
-        }
-
-        if found_in.is_empty() {
-            // not found anywhere
-            undefined.push((
-                anusrit.file.clone(),
-                anusrit.line,
-                anusrit.mantra_text.clone(),
-            ));
+            diagnostics.push(diagnostic);
         } else if found_in.len() > 1 {
             // found in multiple shastras - ambiguous
-            ambiguous.push((
-                anusrit.file.clone(),
-                anusrit.line,
-                anusrit.mantra_text.clone(),
-                found_in,
-            ));
+            let found_in: Vec<&str> = found_in.iter().map(|s| s.as_str()).collect();
+            diagnostics.push(
+                Diagnostic::at(
+                    Rule::AmbiguousAnusrit,
+                    &anusrit.file,
+                    anusrit.span,
+                    format!("^{}^", truncate(&anusrit.mantra_text, 60)),
+                )
+                .note(format!("found in: {}", found_in.join(", ")))
+                .note("use @shastra to disambiguate".to_string()),
+            );
         }
         // found in exactly one external shastra - valid, no error
     }
+}
 
-    (undefined, ambiguous)
+/// Check that every `tyakta: -> _| replacement |_` names a live mantra, here
+/// or (with `@shastra`) in an observed shastra
+fn check_tyakta_replacements(repo: &Repository, resolver: &mut ShastraResolver, diagnostics: &mut Vec<Diagnostic>) {
+    let self_name = repo.config.name.as_deref();
+    for replacement in repo.tyakta_replacements() {
+        let message = match replacement.shastra.as_deref() {
+            Some(shastra_name) if Some(shastra_name) != self_name => match resolver.resolve(shastra_name) {
+                Ok(external) if external.is_live(&replacement.mantra_text) => continue,
+                Ok(_) => format!(
+                    "replacement not found in shastra '{}': ^{}^",
+                    shastra_name,
+                    truncate(&replacement.mantra_text, 30)
+                ),
+                Err(ResolveError::UndefinedAlias(_)) => {
+                    format!("undefined shastra '{}' in replacement @{}", shastra_name, shastra_name)
+                }
+                Err(e) => e.to_string(),
+            },
+            _ if repo.is_live(&replacement.mantra_text) => continue,
+            _ if repo.is_tyakta(&replacement.mantra_text) => {
+                format!("replacement is tyakta itself: ^{}^", truncate(&replacement.mantra_text, 30))
+            }
+            _ => format!("replacement not found: ^{}^", truncate(&replacement.mantra_text, 30)),
+        };
+        let diagnostic = Diagnostic::at(Rule::TyaktaReplacement, &replacement.file, replacement.span, message);
+        diagnostics.push(match &replacement.shastra {
+            Some(shastra_name) => with_alias_file_note(repo, shastra_name, diagnostic),
+            None => diagnostic,
+        });
+    }
+}
+
+// _| check suggests the tyakta replacement |_
+/// Suggest the replacement `shastra` names for `mantra`, if it abandoned the
+/// mantra for one - `alias` is how we know that shastra (None for ourselves)
+fn with_replacement_note(
+    shastra: &Repository,
+    alias: Option<&str>,
+    mantra: &str,
+    diagnostic: Diagnostic,
+) -> Diagnostic {
+    let Some(replacement) = shastra.replacement_for(mantra) else {
+        return diagnostic;
+    };
+    // a replacement in yet another shastra keeps its `@`, one in `shastra` itself needs ours
+    let marker = anusrit_marker(&replacement.mantra_text);
+    let suggestion = match replacement.shastra.as_deref().or(alias) {
+        Some(shastra_name) => format!("{}@{}", marker, shastra_name),
+        None => marker,
+    };
+    let source = alias.map(|a| format!(" in '{}'", a)).unwrap_or_default();
+    diagnostic.note(format!("^{}^ is tyakta{} - use {} instead", truncate(mantra, 30), source, suggestion))
+}
+
+/// Point shastra errors at a broken shastra.json - an alias it failed to
+/// define shows up as "undefined shastra" everywhere it is used
+fn with_alias_file_note(repo: &Repository, alias: &str, diagnostic: Diagnostic) -> Diagnostic {
+    let alias_file_broken = repo
+        .config_errors
+        .iter()
+        .any(|e| e.file.ends_with("shastra.json") || e.file.ends_with("shastra.local.json"));
+    if alias_file_broken && !repo.shastra_config.aliases.contains_key(alias) {
+        diagnostic.note("shastra.json has errors - see the config file errors above".to_string())
+    } else {
+        diagnostic
+    }
 }
 
 fn truncate(s: &str, max_len: usize) -> String {
     let first_line = s.lines().next().unwrap_or(s);
-    if first_line.len() > max_len {
-        format!("{}...", &first_line[..max_len])
+    if first_line.chars().count() > max_len {
+        format!("{}...", first_line.chars().take(max_len).collect::<String>())
     } else if s.contains('\n') {
         format!("{}...", first_line)
     } else {
         first_line.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::mula_marker;
+    use crate::parser::normalize_mantra;
+    use crate::testing::TempShastra;
+
+    #[test]
+    fn truncate_counts_characters() {
+        // a byte slice would cut the second character in half
+        assert_eq!(truncate("धर्म", 2), "धर...");
+        assert_eq!(truncate("short", 60), "short");
+        assert_eq!(truncate("two\nlines", 60), "two...");
+    }
+
+    #[test]
+    fn non_canonical_devanagari_mantra_is_reported() {
+        let mantra = "धर्मक्षेत्रे  कुरुक्षेत्रे समवेता युयुत्सवः मामकाः पाण्डवाश्चैव";
+        let bhasya = format!("> {} - the opening verse.\n", mula_marker(mantra));
+        let shastra = TempShastra::new("gita", &[("gita.md", &bhasya)]);
+        let diagnostics = check(&shastra.parse());
+        let spelling: Vec<_> = diagnostics.iter().filter(|d| d.rule == Rule::NonCanonicalSpelling).collect();
+        assert_eq!(spelling.len(), 1);
+        let canonical = normalize_mantra(mantra);
+        let expected: String = canonical.chars().take(60).collect();
+        assert_eq!(spelling[0].message, format!("^{}...^", expected));
+        assert_eq!(spelling[0].notes, vec!["repeated spaces".to_string()]);
+    }
+}
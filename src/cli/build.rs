@@ -0,0 +1,39 @@
+use vyasa::parser::{find_repo_root, is_site, SITE_MARKER};
+use vyasa::render::Site;
+use vyasa::shastra::ShastraResolver;
+use vyasa::Repository;
+use std::fs;
+use std::path::Path;
+
+// _| vyasa build writes a static site |_
+pub fn run(path: &Path, out: &Path) -> Result<(), String> {
+    // the site covers the whole shastra, wherever in it we are
+    let root = find_repo_root(path).unwrap_or_else(|| path.to_path_buf());
+    // never write pages over a folder that holds something else
+    let occupied = fs::read_dir(out).is_ok_and(|mut entries| entries.next().is_some());
+    if occupied && !is_site(out) {
+        return Err(format!("{} is not empty and was not written by vyasa build", out.display()));
+    }
+    let repo = Repository::parse(&root)?;
+    let mut resolver = ShastraResolver::new(&repo);
+    let mut site = Site::new(&repo, &mut resolver);
+
+    fs::create_dir_all(out).map_err(|e| format!("failed to create {}: {}", out.display(), e))?;
+    let marker = out.join(SITE_MARKER);
+    fs::write(&marker, "written by vyasa build - vyasa never scans this folder\n")
+        .map_err(|e| format!("failed to write {}: {}", marker.display(), e))?;
+    let mut written = 0;
+    for page in site.paths() {
+        let Some(html) = site.render(&page) else {
+            continue;
+        };
+        let target = out.join(&page);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("failed to create {}: {}", parent.display(), e))?;
+        }
+        fs::write(&target, html).map_err(|e| format!("failed to write {}: {}", target.display(), e))?;
+        written += 1;
+    }
+    println!("wrote {} pages to {}", written, out.display());
+    Ok(())
+}
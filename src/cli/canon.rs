@@ -0,0 +1,48 @@
+use vyasa::parser::find_repo_root;
+use vyasa::{canon, Repository};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub fn run(path: &Path, include_uddhrit: bool, check: bool, out: Option<PathBuf>) -> Result<(), String> {
+    // the canon covers the whole shastra, wherever in it we are
+    let root = find_repo_root(path).unwrap_or_else(|| path.to_path_buf());
+    let repo = Repository::parse(&root)?;
+    let digest = canon::render(&repo, include_uddhrit);
+    let out = out.unwrap_or_else(|| root.join("canon.md"));
+
+    if check {
+        let current = fs::read_to_string(&out).unwrap_or_default();
+        if current != digest {
+            return Err(format!("{} is stale - run `vyasa canon` to regenerate it", out.display()));
+        }
+        println!("{} is up to date", out.display());
+        return Ok(());
+    }
+
+    fs::write(&out, &digest).map_err(|e| format!("failed to write {}: {}", out.display(), e))?;
+    let count = canon::entries(&repo, include_uddhrit).len();
+    println!("wrote {} ({} bhasyas)", out.display(), count);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::testing::TempFolder;
+    use vyasa::edit::mula_marker;
+
+    #[test]
+    fn check_reports_a_stale_canon() {
+        let notes = format!("> {} - text.\n", mula_marker("m"));
+        let config = "{\"name\": \"c\"}";
+        let shastra = TempFolder::new("canon-check", &[(".vyasa/config.json", config), ("notes.md", &notes)]);
+        assert!(run(&shastra.root, false, true, None).unwrap_err().contains("is stale"));
+
+        run(&shastra.root, false, false, None).unwrap();
+        assert!(shastra.read("canon.md").contains(&mula_marker("m")));
+        assert!(run(&shastra.root, false, true, None).is_ok());
+
+        shastra.write("notes.md", &format!("{}\n> {} - more.\n", notes, mula_marker("n")));
+        assert!(run(&shastra.root, false, true, None).is_err());
+    }
+}
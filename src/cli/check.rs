@@ -0,0 +1,95 @@
+use vyasa::check::{check, summarize, Diagnostic};
+use vyasa::fix::{self, Fix, Safety};
+use vyasa::Repository;
+use std::path::Path;
+
+// _| vyasa exits with non zero exit code if any rule is violated |_
+pub fn run(path: &Path) -> Result<(), String> {
+    let repo = Repository::parse(path)?;
+    let diagnostics = check(&repo);
+    print_diagnostics(&diagnostics);
+
+    match summarize(&diagnostics) {
+        Some(counts) => Err(counts),
+        None => Ok(()),
+    }
+}
+
+// _| check --fix applies mechanical fixes |_
+/// Apply (or with `diff` only show) the fixes for what check finds, then
+/// check again and report what is left
+pub fn fix(path: &Path, include_unsafe: bool, diff: bool, shastra: Option<&str>) -> Result<(), String> {
+    let repo = Repository::parse(path)?;
+    let diagnostics = check(&repo);
+    let fixes = fix::plan(&repo, &diagnostics, shastra)?;
+    let (applied, skipped): (Vec<&Fix>, Vec<&Fix>) =
+        fixes.iter().partition(|f| f.safety == Safety::Safe || include_unsafe);
+    let changes = fix::changes(&fixes, include_unsafe);
+
+    if diff {
+        let diff = changes.diff()?;
+        if !diff.is_empty() {
+            println!("{}", diff);
+        }
+        print_fixes(&format!("{} fixes", applied.len()), &applied);
+    } else if !changes.is_empty() {
+        let written = changes.write()?;
+        print_fixes(&format!("fixed {} violations in {} files", applied.len(), written), &applied);
+    }
+    if !skipped.is_empty() {
+        print_fixes(&format!("{} unsafe fixes skipped - rerun with --unsafe", skipped.len()), &skipped);
+    }
+    if applied.is_empty() && skipped.is_empty() {
+        println!("nothing to fix\n");
+    }
+    if diff {
+        return match summarize(&diagnostics) {
+            Some(counts) => Err(counts),
+            None => Ok(()),
+        };
+    }
+    run(path)
+}
+
+fn print_fixes(heading: &str, fixes: &[&Fix]) {
+    if fixes.is_empty() {
+        return;
+    }
+    println!("{}:\n", heading);
+    for fix in fixes {
+        println!("  {}:{} {}", fix.file, fix.line, fix.description);
+    }
+    println!();
+}
+
+/// Print diagnostics grouped by rule under a "found N ...:" heading
+pub fn print_diagnostics(diagnostics: &[Diagnostic]) {
+    print_grouped("found", diagnostics);
+}
+
+/// Print diagnostics grouped by rule under a "{verb} N ...:" heading
+pub fn print_grouped(verb: &str, diagnostics: &[Diagnostic]) {
+    for (i, diagnostic) in diagnostics.iter().enumerate() {
+        if i == 0 || diagnostics[i - 1].rule != diagnostic.rule {
+            let count = diagnostics[i..].iter().take_while(|d| d.rule == diagnostic.rule).count();
+            println!("{} {} {}:\n", verb, count, diagnostic.rule.heading());
+        }
+
+        let indent = match (&diagnostic.file, &diagnostic.span) {
+            (Some(file), Some(span)) => {
+                println!("  {}:{}:{}", file, span.start.line, span.start.column);
+                "    "
+            }
+            (Some(file), None) => {
+                println!("  {}", file);
+                "    "
+            }
+            _ => "  ",
+        };
+        println!("{}{}", indent, diagnostic.message);
+        for note in &diagnostic.notes {
+            println!("{}{}", indent, note);
+        }
+        println!();
+    }
+}
@@ -0,0 +1,31 @@
+use vyasa::{fmt, Repository};
+use std::path::Path;
+
+pub fn run(path: &Path, check: bool) -> Result<(), String> {
+    let repo = Repository::parse(path)?;
+    let plan = fmt::plan(&repo)?;
+
+    if !plan.skipped.is_empty() {
+        println!("left {} bhasyas as they are:", plan.skipped.len());
+        for (file, line, why) in &plan.skipped {
+            println!("  {}:{} ({})", file, line, why);
+        }
+        println!();
+    }
+
+    if plan.changes.is_empty() {
+        println!("all bhasyas are formatted");
+        return Ok(());
+    }
+    if check {
+        print!("{}", plan.changes.diff()?);
+        return Err(format!(
+            "{} bhasyas in {} files are not formatted - run `vyasa fmt`",
+            plan.formatted,
+            plan.changes.files().len()
+        ));
+    }
+    let written = plan.changes.write()?;
+    println!("formatted {} bhasyas in {} files", plan.formatted, written);
+    Ok(())
+}
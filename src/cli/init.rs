@@ -0,0 +1,146 @@
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+
+/// Lines `init` makes sure .gitignore has
+const GITIGNORE_ENTRIES: &[&str] = &[".vyasa/shastra.local.json", ".vyasa/cache/"];
+
+/// Starter file written by `--seed`
+const SEED_FILE: &str = "mantras.md";
+
+pub fn run(path: &Path, name: Option<String>, seed: bool) -> Result<(), String> {
+    let vyasa_dir = path.join(".vyasa");
+    fs::create_dir_all(&vyasa_dir)
+        .map_err(|e| format!("failed to create {}: {}", vyasa_dir.display(), e))?;
+
+    // name defaults to the folder name
+    let name = match name {
+        Some(name) => name,
+        None => path
+            .canonicalize()
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+            .ok_or("can't tell the shastra name from the folder - pass --name")?,
+    };
+
+    // config.json - keep what is there, but a shastra must have a name
+    let config_file = vyasa_dir.join("config.json");
+    let mut config = match fs::read_to_string(&config_file) {
+        Ok(content) => match serde_json::from_str::<Value>(&content) {
+            Ok(Value::Object(config)) => config,
+            _ => return Err(format!("{} is not a JSON object - fix or remove it", config_file.display())),
+        },
+        Err(_) => Map::new(),
+    };
+    if let Some(existing) = config.get("name").and_then(|v| v.as_str()) {
+        return Err(format!("already initialized as shastra '{}'", existing));
+    }
+    config.insert("name".to_string(), Value::String(name.clone()));
+    write_json(&config_file, &Value::Object(config))?;
+    println!("wrote {}", config_file.display());
+
+    // shastra.json - no observed shastras yet
+    let shastra_file = vyasa_dir.join("shastra.json");
+    if !shastra_file.exists() {
+        write_json(&shastra_file, &Value::Object(Map::new()))?;
+        println!("wrote {}", shastra_file.display());
+    }
+
+    // local overrides and the parse cache are per machine
+    let gitignore = path.join(".gitignore");
+    let mut content = fs::read_to_string(&gitignore).unwrap_or_default();
+    let missing: Vec<_> = GITIGNORE_ENTRIES
+        .iter()
+        .filter(|entry| !content.lines().any(|line| line.trim() == **entry))
+        .collect();
+    if !missing.is_empty() {
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        for entry in missing {
+            content.push_str(entry);
+            content.push('\n');
+        }
+        fs::write(&gitignore, content).map_err(|e| format!("failed to write {}: {}", gitignore.display(), e))?;
+        println!("updated {}", gitignore.display());
+    }
+
+    if seed {
+        let seed_file = path.join(SEED_FILE);
+        if seed_file.exists() {
+            println!("skipped {} - it already exists", seed_file.display());
+        } else {
+            fs::write(&seed_file, seed_content(&name))
+                .map_err(|e| format!("failed to write {}: {}", seed_file.display(), e))?;
+            println!("wrote {}", seed_file.display());
+        }
+    }
+
+    println!("\ninitialized shastra '{}' - run `vyasa check` to validate it", name);
+    Ok(())
+}
+
+fn write_json(file: &Path, value: &Value) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    fs::write(file, json + "\n").map_err(|e| format!("failed to write {}: {}", file.display(), e))
+}
+
+/// A starter file with one bhasya and one anusrit that passes check - the
+/// anusrit markers are assembled so vyasa's own scan of this source skips them
+fn seed_content(name: &str) -> String {
+    let mantra = format!("{} keeps its knowledge in mantras", name);
+    let anusrit = ["_", "| ", &mantra, " |", "_"].concat();
+    format!(
+        "# {}\n\n> **^{}^** - a mantra is a short, exact statement.\n> the quote block around it is its bhasya: the commentary that explains it.\n\nanywhere else, use the mantra by repeating it exactly:\n{}\n",
+        name, mantra, anusrit
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::testing::TempFolder;
+
+    fn config(folder: &TempFolder) -> Value {
+        serde_json::from_str(&folder.read(".vyasa/config.json")).unwrap()
+    }
+
+    #[test]
+    fn init_names_the_shastra_and_ignores_the_cache() {
+        let folder = TempFolder::new("init-named", &[(".gitignore", "target/")]);
+        run(&folder.root, Some("gita".to_string()), false).unwrap();
+        assert_eq!(config(&folder)["name"], "gita");
+        assert_eq!(folder.read(".vyasa/shastra.json"), "{}\n");
+        assert_eq!(folder.read(".gitignore"), "target/\n.vyasa/shastra.local.json\n.vyasa/cache/\n");
+        assert!(!folder.path(SEED_FILE).exists());
+    }
+
+    #[test]
+    fn the_name_defaults_to_the_folder_name() {
+        let folder = TempFolder::new("init-derived", &[(".vyasa/config.json", "{\"cache\": false}")]);
+        run(&folder.root, None, true).unwrap();
+        let name = folder.root.file_name().unwrap().to_string_lossy().to_string();
+        assert_eq!(config(&folder)["name"], name.as_str());
+        // what was there is kept
+        assert_eq!(config(&folder)["cache"], false);
+        assert!(folder.read(SEED_FILE).contains(&name));
+    }
+
+    #[test]
+    fn an_initialized_shastra_is_left_alone() {
+        let folder = TempFolder::new("init-twice", &[]);
+        run(&folder.root, Some("first".to_string()), false).unwrap();
+        let gitignore = folder.read(".gitignore");
+        let error = run(&folder.root, Some("second".to_string()), false).unwrap_err();
+        assert_eq!(error, "already initialized as shastra 'first'");
+        assert_eq!(config(&folder)["name"], "first");
+        assert_eq!(folder.read(".gitignore"), gitignore);
+    }
+
+    #[test]
+    fn a_config_that_is_not_an_object_is_not_overwritten() {
+        let folder = TempFolder::new("init-broken", &[(".vyasa/config.json", "[1, 2]")]);
+        assert!(run(&folder.root, Some("x".to_string()), false).is_err());
+        assert_eq!(folder.read(".vyasa/config.json"), "[1, 2]");
+    }
+}
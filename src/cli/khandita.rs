@@ -0,0 +1,93 @@
+use vyasa::khandita::{self, KhanditaStatus};
+use vyasa::Repository;
+use std::path::Path;
+
+/// Scaffold a khandita block - printed, or appended to `into`
+pub fn refute(path: &Path, shastra: &str, mantra: &str, into: Option<&Path>, write: bool) -> Result<(), String> {
+    let repo = Repository::parse(path)?;
+    let plan = khandita::plan(&repo, shastra, mantra, into)?;
+
+    let Some(into) = into else {
+        for line in &plan.lines {
+            println!("{}", line);
+        }
+        return Ok(());
+    };
+
+    print!("{}", plan.changes.diff()?);
+    println!(
+        "\n^{}^: khandita of '{}' added to {} - write the refutation after it",
+        plan.mantra,
+        plan.shastra,
+        into.display()
+    );
+    if write {
+        let written = plan.changes.write()?;
+        println!("\nwrote {} files", written);
+    } else {
+        println!("\ndry run - pass --write to apply");
+    }
+    Ok(())
+}
+
+/// List our khanditas - with `status`, grouped by whether the source has
+/// abandoned what they refute
+pub fn list(path: &Path, status: bool) -> Result<(), String> {
+    let repo = Repository::parse(path)?;
+    let khanditas = khandita::status(&repo);
+    if !status {
+        for k in &khanditas {
+            println!("{}:{}:{}  {} ^{}^", k.file, k.span.start.line, k.span.start.column, k.shastra, k.mantra);
+        }
+        return Ok(());
+    }
+
+    let headings = [
+        "pending - the source still teaches it",
+        "resolved - the source abandoned it",
+        "unknown - the source can't be checked",
+    ];
+    let mut counts = Vec::new();
+    for (index, heading) in headings.iter().enumerate() {
+        let group: Vec<_> = khanditas.iter().filter(|k| group_of(&k.status) == index).collect();
+        counts.push(group.len());
+        if group.is_empty() {
+            continue;
+        }
+        println!("{}:", heading);
+        for k in group {
+            println!("  {}:{}:{}  {} ^{}^", k.file, k.span.start.line, k.span.start.column, k.shastra, k.mantra);
+            match &k.status {
+                KhanditaStatus::Resolved(note) => {
+                    if let Some(date) = note.date {
+                        println!("    tyakta since {}", date);
+                    }
+                    if let Some(replacement) = &note.replacement {
+                        println!("    replaced by ^{}^", replacement.mantra_text);
+                    }
+                }
+                KhanditaStatus::Missing => println!("    no such bhasya in '{}'", k.shastra),
+                KhanditaStatus::Unresolved(error) => println!("    {}", error),
+                KhanditaStatus::Pending => {}
+            }
+        }
+        println!();
+    }
+    println!(
+        "{} khanditas: {} pending, {} resolved, {} unknown",
+        khanditas.len(),
+        counts[0],
+        counts[1],
+        counts[2]
+    );
+    Ok(())
+}
+
+/// Index into the `--status` headings
+fn group_of(status: &KhanditaStatus) -> usize {
+    match status {
+        KhanditaStatus::Pending => 0,
+        KhanditaStatus::Resolved(_) => 1,
+        KhanditaStatus::Missing | KhanditaStatus::Unresolved(_) => 2,
+    }
+}
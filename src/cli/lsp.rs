@@ -0,0 +1,10 @@
+use lsp_server::Connection;
+use std::path::Path;
+
+pub fn run(path: &Path) -> Result<(), String> {
+    let (connection, io_threads) = Connection::stdio();
+    vyasa::lsp::serve(&connection, path)?;
+    // the writer thread must finish sending before we exit
+    drop(connection);
+    io_threads.join().map_err(|e| e.to_string())
+}
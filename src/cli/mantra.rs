@@ -1,8 +1,10 @@
-use crate::parser::Repository;
+use vyasa::{normalize_mantra, Repository};
 use std::path::Path;
 
 pub fn run(path: &Path, mantra_text: &str, show_references: bool) -> Result<(), String> {
     let repo = Repository::parse(path)?;
+    let mantra_text = normalize_mantra(mantra_text);
+    let mantra_text = mantra_text.as_str();
 
     // find mantra matching this text
     let found = repo.mantras.get(mantra_text);
@@ -41,7 +43,13 @@ pub fn run(path: &Path, mantra_text: &str, show_references: bool) -> Result<(),
     } else {
         println!("bhasyas ({}):", bhasyas.len());
         for b in bhasyas {
-            println!("  {}:{}", b.file, b.line);
+            // point at the mantra itself - a mula marker, else an anusrit inside the bhasya
+            let span = b.mulas.iter()
+                .find(|m| m.mantra_text == mantra_text)
+                .map(|m| m.span)
+                .or_else(|| b.anusrits.iter().find(|a| a.mantra_text == mantra_text).map(|a| a.span))
+                .unwrap_or(b.span);
+            println!("  {}:{}:{}", b.file, span.start.line, span.start.column);
             // show first line of the original paragraph (truncated)
             if let Some(first_line) = b.paragraph.lines().next() {
                 println!("    {}", truncate(first_line, 70));
@@ -51,7 +59,7 @@ pub fn run(path: &Path, mantra_text: &str, show_references: bool) -> Result<(),
     }
 
     // show the mula definition location
-    println!("mula definition: {}:{}\n", mantra.file, mantra.line);
+    println!("mula definition: {}:{}:{}\n", mantra.file, mantra.line, mantra.span.start.column);
 
     if show_references {
         // find all anusrits to this mantra
@@ -66,7 +74,7 @@ pub fn run(path: &Path, mantra_text: &str, show_references: bool) -> Result<(),
         } else {
             println!("anusrits ({}):", refs.len());
             for r in &refs {
-                println!("  {}:{}", r.file, r.line);
+                println!("  {}:{}:{}", r.file, r.line, r.span.start.column);
             }
         }
     }
@@ -76,8 +84,8 @@ pub fn run(path: &Path, mantra_text: &str, show_references: bool) -> Result<(),
 
 fn truncate(s: &str, max_len: usize) -> String {
     let first_line = s.lines().next().unwrap_or(s);
-    if first_line.len() > max_len {
-        format!("{}...", &first_line[..max_len])
+    if first_line.chars().count() > max_len {
+        format!("{}...", first_line.chars().take(max_len).collect::<String>())
     } else if s.contains('\n') {
         format!("{}...", first_line)
     } else {
@@ -0,0 +1,150 @@
+use vyasa::parser::find_repo_root;
+use vyasa::shastra::{is_alias, is_folder_path, open_shastra, read_aliases, write_aliases, ShastraResolver};
+use vyasa::Repository;
+use std::path::{Path, PathBuf};
+
+/// Add (or replace) an alias, or list aliases when none is given
+pub fn observe(path: &Path, alias: Option<String>, target: Option<String>, local: bool) -> Result<(), String> {
+    let root = shastra_root(path)?;
+    let (Some(alias), Some(target)) = (alias, target) else {
+        return list(&root);
+    };
+    if !is_alias(&alias) {
+        return Err(format!(
+            "alias '{}' may only hold letters, digits, `-` and `_` - anusrits name it after `@`",
+            alias
+        ));
+    }
+
+    // paths are relative to the repository root, like the resolver reads them
+    let target = if is_folder_path(&target) { target } else { format!("./{}", target) };
+    let (name, repo) = open_shastra(&root.join(&target))?;
+    println!("{} is shastra '{}' with {} mantras", target, name, repo.mantras.len());
+    if name != alias {
+        println!("warning: alias '{}' differs from the shastra's own name '{}'", alias, name);
+    }
+
+    let file = alias_file(&root, local);
+    let mut aliases = read_aliases(&file)?;
+    match aliases.insert(alias.clone(), target.clone()) {
+        Some(previous) if previous != target => {
+            println!("'{}' now refers to {} (was {})", alias, target, previous)
+        }
+        _ => println!("observing '{}' at {}", alias, target),
+    }
+    write_aliases(&file, &aliases)
+}
+
+/// Remove an alias
+pub fn unobserve(path: &Path, alias: &str, local: bool) -> Result<(), String> {
+    let root = shastra_root(path)?;
+    let file = alias_file(&root, local);
+    let mut aliases = read_aliases(&file)?;
+    if aliases.remove(alias).is_none() {
+        return Err(format!("'{}' is not in {}", alias, file.display()));
+    }
+    write_aliases(&file, &aliases)?;
+    println!("no longer observing '{}' (removed from {})", alias, file.display());
+    Ok(())
+}
+
+/// Every alias with where it comes from and whether it resolves
+fn list(root: &Path) -> Result<(), String> {
+    let shared = read_aliases(&alias_file(root, false))?;
+    let local = read_aliases(&alias_file(root, true))?;
+    if shared.is_empty() && local.is_empty() {
+        println!("not observing any shastras - add one with `vyasa observe <alias> <path>`");
+        return Ok(());
+    }
+
+    let repo = Repository::parse(root)?;
+    let mut resolver = ShastraResolver::new(&repo);
+    for alias in resolver.aliases() {
+        let source = if local.contains_key(&alias) { " (local)" } else { "" };
+        let target = local.get(&alias).or(shared.get(&alias)).cloned().unwrap_or_default();
+        println!("{} -> {}{}", alias, target, source);
+        match resolver.resolve(&alias) {
+            Ok(external) => println!("  {} mantras", external.mantras.len()),
+            Err(e) => println!("  error: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Root of the shastra holding `path` - it must already have a `.vyasa` folder
+fn shastra_root(path: &Path) -> Result<PathBuf, String> {
+    find_repo_root(path)
+        .filter(|root| root.join(".vyasa").is_dir())
+        .ok_or_else(|| format!("{} is not in a shastra - run `vyasa init` first", path.display()))
+}
+
+fn alias_file(root: &Path, local: bool) -> PathBuf {
+    let name = if local { "shastra.local.json" } else { "shastra.json" };
+    root.join(".vyasa").join(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::testing::TempFolder;
+
+    /// A shastra named `name`
+    fn shastra(name: &str) -> TempFolder {
+        TempFolder::new(name, &[(".vyasa/config.json", &format!("{{\"name\": \"{}\"}}", name))])
+    }
+
+    fn aliases(folder: &TempFolder, file: &str) -> serde_json::Value {
+        serde_json::from_str(&folder.read(file)).unwrap()
+    }
+
+    fn at(folder: &TempFolder) -> Option<String> {
+        Some(folder.root.to_string_lossy().to_string())
+    }
+
+    #[test]
+    fn observing_keeps_the_other_aliases() {
+        let (ours, gita, vedas) = (shastra("observer"), shastra("gita"), shastra("vedas"));
+        ours.write(".vyasa/shastra.json", "{\"old\": \"./old\"}");
+        observe(&ours.root, Some("gita".to_string()), at(&gita), false).unwrap();
+        observe(&ours.root, Some("vedas".to_string()), at(&vedas), false).unwrap();
+        let written = aliases(&ours, ".vyasa/shastra.json");
+        assert_eq!(written["old"], "./old");
+        assert_eq!(written["gita"], at(&gita).unwrap().as_str());
+        assert_eq!(written["vedas"], at(&vedas).unwrap().as_str());
+    }
+
+    #[test]
+    fn local_aliases_go_to_the_local_file() {
+        let (ours, gita) = (shastra("observer-local"), shastra("gita"));
+        observe(&ours.root, Some("gita".to_string()), at(&gita), true).unwrap();
+        assert_eq!(aliases(&ours, ".vyasa/shastra.local.json")["gita"], at(&gita).unwrap().as_str());
+        assert!(!ours.path(".vyasa/shastra.json").exists());
+
+        unobserve(&ours.root, "gita", true).unwrap();
+        assert_eq!(aliases(&ours, ".vyasa/shastra.local.json"), serde_json::json!({}));
+    }
+
+    #[test]
+    fn observing_an_alias_again_points_it_elsewhere() {
+        let (ours, first, second) = (shastra("observer-again"), shastra("first"), shastra("second"));
+        observe(&ours.root, Some("notes".to_string()), at(&first), false).unwrap();
+        observe(&ours.root, Some("notes".to_string()), at(&second), false).unwrap();
+        assert_eq!(aliases(&ours, ".vyasa/shastra.json"), serde_json::json!({ "notes": at(&second) }));
+    }
+
+    #[test]
+    fn bad_aliases_and_targets_are_refused() {
+        let (ours, gita) = (shastra("observer-refuses"), TempFolder::new("not-a-shastra", &[]));
+        assert!(observe(&ours.root, Some("my gita".to_string()), at(&gita), false).unwrap_err().contains("may only"));
+        assert!(observe(&ours.root, Some("gita".to_string()), at(&gita), false).unwrap_err().contains("not a shastra"));
+        assert!(!ours.path(".vyasa/shastra.json").exists());
+    }
+
+    #[test]
+    fn unobserving_an_unknown_alias_is_an_error() {
+        let ours = shastra("observer-unknown");
+        ours.write(".vyasa/shastra.json", "{\"gita\": \"./gita\"}");
+        assert!(unobserve(&ours.root, "vedas", false).unwrap_err().starts_with("'vedas' is not in"));
+        assert_eq!(aliases(&ours, ".vyasa/shastra.json"), serde_json::json!({ "gita": "./gita" }));
+    }
+}
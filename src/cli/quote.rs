@@ -0,0 +1,24 @@
+use vyasa::{quote, Repository};
+use std::path::Path;
+
+pub fn run(path: &Path, shastra: &str, mantra: &str, into: Option<&Path>, write: bool) -> Result<(), String> {
+    let repo = Repository::parse(path)?;
+    let plan = quote::plan(&repo, shastra, mantra, into)?;
+
+    let Some(into) = into else {
+        for line in &plan.lines {
+            println!("{}", line);
+        }
+        return Ok(());
+    };
+
+    print!("{}", plan.changes.diff()?);
+    println!("\n^{}^: quoted from {} {} into {}", plan.mantra, plan.shastra, plan.source, into.display());
+    if write {
+        let written = plan.changes.write()?;
+        println!("\nwrote {} files", written);
+    } else {
+        println!("\ndry run - pass --write to apply");
+    }
+    Ok(())
+}
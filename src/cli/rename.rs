@@ -0,0 +1,35 @@
+use vyasa::{rename, Repository};
+use std::path::Path;
+
+pub fn run(path: &Path, old: &str, new: &str, write: bool, tyakta: bool) -> Result<(), String> {
+    let repo = Repository::parse(path)?;
+    let plan = rename::plan(&repo, old, new, tyakta)?;
+
+    print!("{}", plan.changes.diff()?);
+    println!(
+        "\n^{}^ -> ^{}^: {} mula definitions, {} anusrits in {} files",
+        plan.old,
+        plan.new,
+        plan.mulas,
+        plan.anusrits,
+        plan.changes.files().len()
+    );
+
+    if !plan.external.is_empty() {
+        println!("\n{} uses in observed shastras will break - update them there:", plan.external.len());
+        for external in &plan.external {
+            println!(
+                "  {}: {}:{}:{} ({})",
+                external.shastra, external.file, external.span.start.line, external.span.start.column, external.kind
+            );
+        }
+    }
+
+    if write {
+        let written = plan.changes.write()?;
+        println!("\nwrote {} files", written);
+    } else {
+        println!("\ndry run - pass --write to apply");
+    }
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use super::watch::{collect, SETTLE};
+use notify::{RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use tiny_http::{Header, Response, Server};
+use vyasa::watch::Watch;
+
+/// The shastra as last parsed, and how many times it has changed
+struct State {
+    watch: Watch,
+    generation: u64,
+}
+
+/// Path pages poll to learn the shastra changed
+const VERSION: &str = "/__vyasa/version";
+
+// _| vyasa serve reloads on change |_
+/// Reloads the page once the generation moves past the one it was served with
+fn reload_script(generation: u64) -> String {
+    format!(
+        "<script>\nsetInterval(() => fetch('{VERSION}').then(r => r.text())\n  \
+         .then(v => {{ if (v !== '{generation}') location.reload(); }}).catch(() => {{}}), 1000);\n</script>\n"
+    )
+}
+
+pub fn run(path: &Path, port: u16) -> Result<(), String> {
+    let state = Arc::new(Mutex::new(State { watch: Watch::new(path)?, generation: 0 }));
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(|e| format!("can't watch files: {}", e))?;
+    let folder = state.lock().unwrap().watch.folder().to_path_buf();
+    watcher
+        .watch(&folder, RecursiveMode::Recursive)
+        .map_err(|e| format!("can't watch {}: {}", path.display(), e))?;
+    let watched = Arc::clone(&state);
+    thread::spawn(move || {
+        while let Ok(event) = receiver.recv() {
+            let mut changed = Vec::new();
+            collect(event, &mut changed);
+            while let Ok(event) = receiver.recv_timeout(SETTLE) {
+                collect(event, &mut changed);
+            }
+            let mut state = watched.lock().unwrap();
+            match state.watch.update(&changed) {
+                Ok(update) if update.files.is_empty() && !update.reloaded => {}
+                Ok(_) => state.generation += 1,
+                Err(e) => eprintln!("error: {}", e),
+            }
+        }
+    });
+
+    let server = Server::http(("127.0.0.1", port)).map_err(|e| format!("can't listen on port {}: {}", port, e))?;
+    println!("serving {} at http://127.0.0.1:{}/", path.display(), port);
+    for request in server.incoming_requests() {
+        let mut state = state.lock().unwrap();
+        let url = request.url().split(['?', '#']).next().unwrap_or("/").to_string();
+        let response = if url == VERSION {
+            Response::from_string(state.generation.to_string())
+        } else {
+            let page = match percent_decode(url.trim_start_matches('/')) {
+                page if page.is_empty() => "index.html".to_string(),
+                page => page,
+            };
+            let generation = state.generation;
+            let mut site = state.watch.site();
+            site.script = reload_script(generation);
+            match site.render(&page) {
+                Some(html) => Response::from_string(html)
+                    .with_header(Header::from_bytes("Content-Type", "text/html; charset=utf-8").unwrap()),
+                None => Response::from_string("not found").with_status_code(404),
+            }
+        };
+        drop(state);
+        // the browser going away mid-response is its business
+        let _ = request.respond(response);
+    }
+    Ok(())
+}
+
+/// `%xx` escapes in a url path, as browsers send non-ascii file names
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
@@ -1,4 +1,4 @@
-use crate::parser::{Repository, BhasyaKind};
+use vyasa::{Repository, BhasyaKind};
 use std::path::Path;
 
 pub fn run(path: &Path) -> Result<(), String> {
@@ -15,7 +15,7 @@ pub fn run(path: &Path) -> Result<(), String> {
 
     for bhasya in &repo.bhasyas {
         match &bhasya.kind {
-            BhasyaKind::Tyakta => tyakta_count += 1,
+            BhasyaKind::Tyakta(_) => tyakta_count += 1,
             BhasyaKind::Uddhrit(_) => uddhrit_count += 1,
             BhasyaKind::Khandita(_) => khandita_count += 1,
             BhasyaKind::Mula => bhasya_count += 1,
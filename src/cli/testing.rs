@@ -0,0 +1,47 @@
+//! Throwaway folders for the command tests
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// A fresh temporary folder, removed when dropped
+pub struct TempFolder {
+    pub root: PathBuf,
+}
+
+impl TempFolder {
+    /// A folder holding `files`, given as (path from the root, content)
+    pub fn new(name: &str, files: &[(&str, &str)]) -> Self {
+        let id = NEXT.fetch_add(1, Ordering::SeqCst);
+        let root = std::env::temp_dir().join(format!("vyasa-cli-test-{}-{}-{}", std::process::id(), name, id));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let folder = TempFolder { root };
+        for (path, content) in files {
+            folder.write(path, content);
+        }
+        folder
+    }
+
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    pub fn write(&self, relative: &str, content: &str) {
+        let path = self.path(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    pub fn read(&self, relative: &str) -> String {
+        fs::read_to_string(self.path(relative)).unwrap()
+    }
+}
+
+impl Drop for TempFolder {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
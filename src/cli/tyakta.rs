@@ -0,0 +1,49 @@
+use vyasa::{tyakta, Repository};
+use std::path::Path;
+
+pub fn run(path: &Path, mantra: &str, replacement: Option<&str>, write: bool) -> Result<(), String> {
+    let repo = Repository::parse(path)?;
+    let plan = tyakta::plan(&repo, mantra, replacement)?;
+
+    print!("{}", plan.changes.diff()?);
+    match &plan.replacement {
+        Some(replacement) => println!(
+            "\n^{}^: {} bhasyas marked tyakta, replaced by ^{}^",
+            plan.mantra, plan.bhasyas, replacement
+        ),
+        None => println!("\n^{}^: {} bhasyas marked tyakta", plan.mantra, plan.bhasyas),
+    }
+
+    if !plan.also_deprecated.is_empty() {
+        println!("\nthese mantras share a bhasya with it and are marked tyakta there too:");
+        for mantra in &plan.also_deprecated {
+            println!("  ^{}^", mantra);
+        }
+    }
+
+    // _| anusrits to tyakta-only mantras are invalid |_
+    if !plan.broken_anusrits.is_empty() {
+        println!("\n{} anusrits in this shastra will become undefined:", plan.broken_anusrits.len());
+        for anusrit in &plan.broken_anusrits {
+            println!("  {}:{}:{}", anusrit.file, anusrit.span.start.line, anusrit.span.start.column);
+        }
+    }
+
+    if !plan.downstream.is_empty() {
+        println!("\n{} uses in observed shastras will break:", plan.downstream.len());
+        for external in &plan.downstream {
+            println!(
+                "  {}: {}:{}:{} ({})",
+                external.shastra, external.file, external.span.start.line, external.span.start.column, external.kind
+            );
+        }
+    }
+
+    if write {
+        let written = plan.changes.write()?;
+        println!("\nwrote {} files", written);
+    } else {
+        println!("\ndry run - pass --write to apply");
+    }
+    Ok(())
+}
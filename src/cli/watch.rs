@@ -0,0 +1,65 @@
+use super::check::{print_diagnostics, print_grouped};
+use chrono::Local;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+use vyasa::check::{summarize, Diagnostic};
+use vyasa::watch::Watch;
+
+/// How long to wait for more events once files start changing - editors
+/// and `git checkout` write in bursts
+pub const SETTLE: Duration = Duration::from_millis(100);
+
+// _| vyasa watch prints what changed |_
+pub fn run(path: &Path) -> Result<(), String> {
+    let mut watch = Watch::new(path)?;
+    print_diagnostics(watch.diagnostics());
+    print_status(watch.diagnostics());
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(|e| format!("can't watch files: {}", e))?;
+    watcher
+        .watch(watch.folder(), RecursiveMode::Recursive)
+        .map_err(|e| format!("can't watch {}: {}", path.display(), e))?;
+    println!("watching {} for changes", path.display());
+
+    while let Ok(event) = receiver.recv() {
+        let mut changed = Vec::new();
+        collect(event, &mut changed);
+        while let Ok(event) = receiver.recv_timeout(SETTLE) {
+            collect(event, &mut changed);
+        }
+
+        let update = watch.update(&changed)?;
+        if update.files.is_empty() && !update.reloaded {
+            continue;
+        }
+        println!();
+        match update.reloaded {
+            true => println!("[{}] config changed - parsed everything again", Local::now().format("%H:%M:%S")),
+            false => println!("[{}] changed {}", Local::now().format("%H:%M:%S"), update.files.join(", ")),
+        }
+        println!();
+        print_grouped("new", &update.new);
+        print_grouped("resolved", &update.resolved);
+        print_status(watch.diagnostics());
+    }
+    Ok(())
+}
+
+/// Paths written, created or removed - reads are our own parsing
+pub fn collect(event: notify::Result<Event>, changed: &mut Vec<PathBuf>) {
+    if let Ok(event) = event {
+        if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+            changed.extend(event.paths);
+        }
+    }
+}
+
+fn print_status(diagnostics: &[Diagnostic]) {
+    match summarize(diagnostics) {
+        Some(counts) => println!("{}", counts),
+        None => println!("no violations"),
+    }
+}
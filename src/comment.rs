@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Comment syntax for one kind of file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommentSyntax {
+    /// Line comment prefixes, most specific first (`///` before `//`)
+    #[serde(default)]
+    pub line: Vec<String>,
+    /// Block comments, longer openers first (`/**` before `/*`)
+    #[serde(default)]
+    pub block: Vec<BlockComment>,
+    /// Also read rust `#[doc = "..."]` / `#![doc = "..."]` attributes as comments
+    #[serde(default)]
+    pub doc_attribute: bool,
+}
+
+/// A block comment's delimiters, written as `["/*", "*/"]` in config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "(String, String)", into = "(String, String)")]
+pub struct BlockComment {
+    pub open: String,
+    pub close: String,
+}
+
+impl From<(String, String)> for BlockComment {
+    fn from((open, close): (String, String)) -> Self {
+        BlockComment { open, close }
+    }
+}
+
+impl From<BlockComment> for (String, String) {
+    fn from(block: BlockComment) -> Self {
+        (block.open, block.close)
+    }
+}
+
+impl BlockComment {
+    /// Decoration that may start each continuation line - ` * ` inside `/* */`
+    /// and `(* *)`, i.e. whenever the closing delimiter starts with `*`
+    pub fn continuation(&self) -> Option<char> {
+        self.close.starts_with('*').then_some('*')
+    }
+}
+
+const C_LINE: &[&str] = &["//"];
+const C_BLOCK: &[(&str, &str)] = &[("/**", "*/"), ("/*!", "*/"), ("/*", "*/")];
+const HASH_LINE: &[&str] = &["#"];
+const HTML_BLOCK: &[(&str, &str)] = &[("<!--", "-->")];
+
+impl CommentSyntax {
+    fn new(line: &[&str], block: &[(&str, &str)]) -> Self {
+        CommentSyntax {
+            line: line.iter().map(|s| s.to_string()).collect(),
+            block: block
+                .iter()
+                .map(|(open, close)| BlockComment { open: open.to_string(), close: close.to_string() })
+                .collect(),
+            doc_attribute: false,
+        }
+    }
+
+    /// Every known syntax - used for files whose type is not in the table
+    pub fn any() -> Self {
+        CommentSyntax::new(
+            &["///", "//!", "//", "#", "--", ";", "%"],
+            &[
+                ("/**", "*/"),
+                ("/*!", "*/"),
+                ("/*", "*/"),
+                ("<!--", "-->"),
+                ("(*", "*)"),
+                ("{-", "-}"),
+                ("\"\"\"", "\"\"\""),
+                ("'''", "'''"),
+            ],
+        )
+    }
+
+    /// Built-in syntax for a file type, keyed by lowercase extension (or file
+    /// name for extensionless files like `makefile`)
+    pub fn builtin(key: &str) -> Option<Self> {
+        let syntax = match key {
+            // prose: only html comments, so `# heading` and `---` stay prose
+            "md" | "markdown" | "mdx" | "html" | "htm" | "vue" | "svelte" => {
+                CommentSyntax::new(&[], HTML_BLOCK)
+            }
+            "txt" => CommentSyntax::new(&[], &[]),
+            // rust doc comments first, so `///` and `//!` don't leave a stray `/` or `!`
+            "rs" => CommentSyntax {
+                doc_attribute: true,
+                ..CommentSyntax::new(&["///", "//!", "//"], C_BLOCK)
+            },
+            // c family
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "java" | "js" | "mjs" | "cjs"
+            | "jsx" | "ts" | "tsx" | "go" | "swift" | "kt" | "kts" | "scala" | "cs" | "dart"
+            | "groovy" | "gradle" | "proto" | "scss" | "less" | "zig" => {
+                CommentSyntax::new(C_LINE, C_BLOCK)
+            }
+            "css" => CommentSyntax::new(&[], C_BLOCK),
+            "php" => CommentSyntax::new(&["//", "#"], C_BLOCK),
+            // hash family
+            "py" | "pyi" => CommentSyntax::new(HASH_LINE, &[("\"\"\"", "\"\"\""), ("'''", "'''")]),
+            "sh" | "bash" | "zsh" | "fish" | "rb" | "pl" | "pm" | "r" | "yaml" | "yml" | "toml"
+            | "cfg" | "conf" | "mk" | "makefile" | "dockerfile" | "cmake" | "ex" | "exs" | "jl"
+            | "tf" | "ps1" | "gitignore" => CommentSyntax::new(HASH_LINE, &[]),
+            "nix" => CommentSyntax::new(HASH_LINE, &[("/*", "*/")]),
+            "ini" => CommentSyntax::new(&[";", "#"], &[]),
+            // dash-dash family
+            "sql" => CommentSyntax::new(&["--"], &[("/*", "*/")]),
+            "lua" => CommentSyntax::new(&["--"], &[("--[[", "]]")]),
+            "hs" | "lhs" | "elm" | "purs" => CommentSyntax::new(&["--"], &[("{-", "-}")]),
+            "ml" | "mli" | "fs" | "fsi" => CommentSyntax::new(&[], &[("(*", "*)")]),
+            // lisps and percent
+            "el" | "lisp" | "cl" | "clj" | "cljs" | "scm" | "rkt" | "asm" => {
+                CommentSyntax::new(&[";"], &[])
+            }
+            "tex" | "sty" | "erl" | "hrl" => CommentSyntax::new(&["%"], &[]),
+            _ => return None,
+        };
+        Some(syntax)
+    }
+
+    /// Syntax for a file type: config overrides first, then the built-in table,
+    /// then every known syntax
+    pub fn for_key(key: &str, overrides: &HashMap<String, CommentSyntax>) -> Self {
+        overrides
+            .get(key)
+            .cloned()
+            .or_else(|| CommentSyntax::builtin(key))
+            .unwrap_or_else(CommentSyntax::any)
+    }
+
+    /// Strip a line comment prefix, returning (content after prefix, prefix
+    /// including indentation), or None if the line is not a line comment
+    pub fn strip_line_prefix<'a>(&self, line: &'a str) -> Option<(&'a str, &'a str)> {
+        let trimmed = line.trim_start();
+
+        // try each comment prefix in order of specificity
+        for prefix in &self.line {
+            if let Some(rest) = trimmed.strip_prefix(prefix.as_str()) {
+                // calculate where the prefix starts in original line
+                let prefix_start = line.len() - trimmed.len();
+                let prefix_end = prefix_start + prefix.len();
+                return Some((rest, &line[..prefix_end]));
+            }
+        }
+
+        None
+    }
+
+    /// Find the string of a `#[doc = "..."]` attribute, returning (byte range of
+    /// the string contents, attribute opener including indentation, whether the
+    /// contents have escapes to resolve - those of a raw `r#"..."#` string don't)
+    pub fn doc_attribute<'a>(&self, line: &'a str) -> Option<(std::ops::Range<usize>, &'a str, bool)> {
+        if !self.doc_attribute {
+            return None;
+        }
+        let trimmed = line.trim();
+        let indent = line.len() - line.trim_start().len();
+        let opener = ["#[doc", "#![doc"].into_iter().find(|o| trimmed.starts_with(o))?;
+        let rest = trimmed[opener.len()..].trim_start().strip_prefix('=')?.trim_start();
+        let (quote, value) = match rest.strip_prefix('r') {
+            Some(raw) => {
+                let hashes = raw.len() - raw.trim_start_matches('#').len();
+                let close = format!("\"{}]", "#".repeat(hashes));
+                (1 + hashes + 1, raw[hashes..].strip_prefix('"')?.strip_suffix(close.as_str())?)
+            }
+            None => (1, rest.strip_prefix('"')?.strip_suffix("\"]")?),
+        };
+        let start = indent + (trimmed.len() - rest.len()) + quote;
+        Some((start..start + value.len(), &line[..indent + opener.len()], quote == 1))
+    }
+}
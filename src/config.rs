@@ -0,0 +1,474 @@
+use crate::comment::CommentSyntax;
+use crate::parser::{Position, Span};
+use globset::Glob;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+/// Repository configuration loaded from .vyasa/config.json
+#[derive(Debug)]
+pub struct Config {
+    /// Name of this shastra (used for self-references)
+    pub name: Option<String>,
+    /// Comment syntax overrides, keyed by file extension
+    pub comments: HashMap<String, CommentSyntax>,
+    /// Globs (relative to the repository root) a file must match to be scanned -
+    /// empty means every file
+    pub include: Vec<String>,
+    /// Globs of files never scanned - the root `canon.md` and those configured
+    pub exclude: Vec<String>,
+    /// Keep parsed files in .vyasa/cache/ (default true)
+    pub cache: bool,
+    /// How `vyasa fmt` lays out bhasyas
+    pub fmt: FmtConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            name: None,
+            comments: HashMap::new(),
+            include: Vec::new(),
+            exclude: default_exclude(),
+            cache: true,
+            fmt: FmtConfig::default(),
+        }
+    }
+}
+
+/// The `fmt` key of config.json
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FmtConfig {
+    /// Written between a mula marker and its commentary (default `" - "`)
+    pub separator: String,
+    /// Longest line, comment markers and `> ` included (default 80)
+    pub width: usize,
+}
+
+impl Default for FmtConfig {
+    fn default() -> Self {
+        FmtConfig { separator: " - ".to_string(), width: 80 }
+    }
+}
+
+/// canon.md at the root is the digest `vyasa canon` writes, not a source
+fn default_exclude() -> Vec<String> {
+    vec!["canon.md".to_string()]
+}
+
+impl Config {
+    /// Comment syntax for a file, chosen by its extension (or its name, for
+    /// extensionless files like `Makefile`)
+    pub fn comment_syntax(&self, file_path: &Path) -> CommentSyntax {
+        let key = file_path
+            .extension()
+            .or_else(|| file_path.file_name())
+            .map(|k| k.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        CommentSyntax::for_key(&key, &self.comments)
+    }
+}
+
+/// Shastra configuration loaded from .vyasa/shastra.json
+/// Maps alias names to paths (local folders)
+#[derive(Debug, Default)]
+pub struct ShastraConfig {
+    /// alias -> path mapping (merged from shastra.json and shastra.local.json)
+    pub aliases: HashMap<String, String>,
+}
+
+/// A problem in a `.vyasa` config file - the valid parts are still used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub file: String,
+    /// Where in the file - the offending key, or the offending value for
+    /// type errors
+    pub span: Span,
+    pub message: String,
+}
+
+/// Keys allowed at the top of config.json
+const CONFIG_KEYS: &[&str] = &["name", "comments", "include", "exclude", "cache", "fmt"];
+
+/// Keys allowed in each `comments` entry
+const COMMENT_KEYS: &[&str] = &["line", "block", "doc_attribute"];
+
+/// Load .vyasa/config.json, reporting (rather than silently dropping) invalid JSON,
+/// unknown keys and values of the wrong type
+pub fn load_config(repo_root: &Path) -> (Config, Vec<ConfigError>) {
+    let mut config = Config::default();
+    let path = repo_root.join(".vyasa/config.json");
+    let Some((source, json)) = read_json(&path) else {
+        return (config, Vec::new());
+    };
+    let mut errors = Vec::new();
+    let object = match json {
+        Ok(Value::Object(object)) => object,
+        Ok(_) => {
+            errors.push(source.error(0, "expected a JSON object".to_string()));
+            return (config, errors);
+        }
+        Err(error) => {
+            errors.push(error);
+            return (config, errors);
+        }
+    };
+
+    for (key, value) in &object {
+        let path = [key.as_str()];
+        match key.as_str() {
+            "name" => config.name = source.field(value, &path, &mut errors),
+            "include" => config.include = source.globs(value, key, &mut errors),
+            // added to the default, so canon.md is never scanned
+            "exclude" => config.exclude.extend(source.globs(value, key, &mut errors)),
+            "cache" => config.cache = source.field(value, &path, &mut errors).unwrap_or(true),
+            "comments" => config.comments = source.comments(value, &mut errors),
+            "fmt" => config.fmt = source.field(value, &path, &mut errors).unwrap_or_default(),
+            _ => errors.push(source.unknown_key(&path, CONFIG_KEYS)),
+        }
+    }
+
+    (config, errors)
+}
+
+/// Load .vyasa/shastra.json and then .vyasa/shastra.local.json (whose aliases
+/// override), reporting entries that are not `"alias": "path"` strings
+pub fn load_shastra_config(repo_root: &Path) -> (ShastraConfig, Vec<ConfigError>) {
+    let mut config = ShastraConfig::default();
+    let mut errors = Vec::new();
+
+    for name in ["shastra.json", "shastra.local.json"] {
+        let path = repo_root.join(".vyasa").join(name);
+        let Some((source, json)) = read_json(&path) else {
+            continue;
+        };
+        match json {
+            Ok(Value::Object(object)) => {
+                for (alias, value) in object {
+                    let at = source.key(&[&alias]);
+                    match value {
+                        Value::String(path) => {
+                            config.aliases.insert(alias, path);
+                        }
+                        _ => errors.push(source.error(
+                            at,
+                            format!("shastra '{}' must map to a folder path string", alias),
+                        )),
+                    }
+                }
+            }
+            Ok(_) => errors.push(source.error(0, "expected a JSON object mapping alias to folder path".to_string())),
+            Err(error) => errors.push(error),
+        }
+    }
+
+    (config, errors)
+}
+
+/// Read and parse a JSON file - None if it does not exist
+fn read_json(path: &Path) -> Option<(Source, Result<Value, ConfigError>)> {
+    let content = fs::read_to_string(path).ok()?;
+    let source = Source { file: path.to_string_lossy().to_string(), content };
+    let json = serde_json::from_str(&source.content).map_err(|e| {
+        // serde_json reports 1-based line and column
+        let byte = line_column(&source.content, e.line(), e.column());
+        source.error(byte, format!("invalid JSON: {}", without_location(&e)))
+    });
+    Some((source, json))
+}
+
+/// A config file's text, for locating errors in it
+struct Source {
+    file: String,
+    content: String,
+}
+
+impl Source {
+    fn error(&self, byte: usize, message: String) -> ConfigError {
+        let byte = byte.min(self.content.len());
+        let before = &self.content[..byte];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let position = Position {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            byte,
+        };
+        ConfigError { file: self.file.clone(), span: Span { start: position, end: position }, message }
+    }
+
+    /// Byte offset of the key at `path` - `["comments", "rs"]` is the `"rs"`
+    /// inside the top-level `"comments"`, not the first `"rs"` in the file
+    fn key(&self, path: &[&str]) -> usize {
+        self.locate(path).map_or(0, |(key, _)| key)
+    }
+
+    /// Where the key at `path` starts and the bytes of its value, looked up
+    /// one nesting level at a time
+    fn locate(&self, path: &[&str]) -> Option<(usize, Range<usize>)> {
+        let text = &self.content;
+        let mut value = skip_space(text, 0)..value_end(text, skip_space(text, 0))?;
+        let mut key_at = 0;
+        for name in path {
+            let mut at = value.start;
+            if !text[at..].starts_with('{') {
+                return None;
+            }
+            loop {
+                at = skip_space(text, at + 1);
+                let key_end = string_end(text, at)?;
+                let key: String = serde_json::from_str(&text[at..key_end]).ok()?;
+                let colon = skip_space(text, key_end);
+                let start = skip_space(text, colon + 1);
+                let end = value_end(text, start)?;
+                if key == *name {
+                    (key_at, value) = (at, start..end);
+                    break;
+                }
+                at = skip_space(text, end);
+                if !text[at..].starts_with(',') {
+                    return None;
+                }
+            }
+        }
+        Some((key_at, value))
+    }
+
+    /// Deserialize the value at `path`, reporting a type error where in the
+    /// value it is - a nested field's own position, not the top-level key
+    fn field<T: DeserializeOwned>(&self, value: &Value, path: &[&str], errors: &mut Vec<ConfigError>) -> Option<T> {
+        let error = match serde_json::from_value(value.clone()) {
+            Ok(value) => return Some(value),
+            Err(e) => e,
+        };
+        // read again from the text for where the error is - unless the text
+        // fails on something else, like an unknown key already reported
+        let at = self
+            .locate(path)
+            .and_then(|(_, range)| {
+                let text = &self.content[range.clone()];
+                let e = serde_json::from_str::<T>(text).err()?;
+                (without_location(&e) == error.to_string())
+                    .then(|| range.start + line_column(text, e.line(), e.column()))
+            })
+            .unwrap_or_else(|| self.key(path));
+        errors.push(self.error(at, format!("'{}': {}", path.join("."), error)));
+        None
+    }
+
+    /// A list of globs, leaving out (and reporting where it is) each one that
+    /// does not parse
+    fn globs(&self, value: &Value, key: &str, errors: &mut Vec<ConfigError>) -> Vec<String> {
+        let patterns: Vec<String> = self.field(value, &[key], errors).unwrap_or_default();
+        let list = self.locate(&[key]).map(|(_, range)| range);
+        patterns
+            .into_iter()
+            .filter(|pattern| {
+                let Err(e) = Glob::new(pattern) else {
+                    return true;
+                };
+                let written = serde_json::to_string(pattern).unwrap_or_default();
+                let at = list
+                    .clone()
+                    .and_then(|range| self.content[range.clone()].find(&written).map(|i| range.start + i))
+                    .unwrap_or_else(|| self.key(&[key]));
+                errors.push(self.error(at, format!("'{}': invalid glob '{}': {}", key, pattern, e.kind())));
+                false
+            })
+            .collect()
+    }
+
+    /// The `comments` map, checking each entry's keys before its types
+    fn comments(&self, value: &Value, errors: &mut Vec<ConfigError>) -> HashMap<String, CommentSyntax> {
+        let mut comments = HashMap::new();
+        let Value::Object(entries) = value else {
+            let message = "'comments' must map file extensions to comment syntax".to_string();
+            errors.push(self.error(self.key(&["comments"]), message));
+            return comments;
+        };
+        for (extension, entry) in entries {
+            let mut entry = entry.clone();
+            if let Value::Object(fields) = &mut entry {
+                let unknown: Vec<String> =
+                    fields.keys().filter(|k| !COMMENT_KEYS.contains(&k.as_str())).cloned().collect();
+                for key in unknown {
+                    errors.push(self.unknown_key(&["comments", extension, &key], COMMENT_KEYS));
+                    fields.remove(&key);
+                }
+            }
+            if let Some(syntax) = self.field(&entry, &["comments", extension], errors) {
+                comments.insert(extension.clone(), syntax);
+            }
+        }
+        comments
+    }
+
+    /// An unknown key at `path`, reported at the key
+    fn unknown_key(&self, path: &[&str], known: &[&str]) -> ConfigError {
+        let key = path.last().copied().unwrap_or_default();
+        let message = match suggest(key, known) {
+            Some(suggestion) => format!("unknown key '{}' - did you mean '{}'?", key, suggestion),
+            None => format!("unknown key '{}' - expected one of: {}", key, known.join(", ")),
+        };
+        self.error(self.key(path), message)
+    }
+}
+
+/// Byte offset in `text` of serde_json's 1-based line and column
+fn line_column(text: &str, line: usize, column: usize) -> usize {
+    let line_start =
+        text.match_indices('\n').nth(line.saturating_sub(2)).filter(|_| line > 1).map_or(0, |(i, _)| i + 1);
+    line_start + column.saturating_sub(1)
+}
+
+/// A serde_json error without its `at line .. column ..` - the location is
+/// reported separately
+fn without_location(error: &serde_json::Error) -> String {
+    let message = error.to_string();
+    message.rsplit_once(" at line ").map_or(message.clone(), |(m, _)| m.to_string())
+}
+
+/// The first byte at or after `at` that isn't JSON whitespace
+fn skip_space(text: &str, at: usize) -> usize {
+    at + text.get(at..).map_or(0, |rest| rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len())
+}
+
+/// The end of the JSON string starting at `at`, its closing quote included
+fn string_end(text: &str, at: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    if bytes.get(at) != Some(&b'"') {
+        return None;
+    }
+    let mut i = at + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// The end of the JSON value starting at `at`
+fn value_end(text: &str, at: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    match bytes.get(at)? {
+        b'"' => string_end(text, at),
+        b'{' | b'[' => {
+            let mut depth = 0;
+            let mut i = at;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'"' => {
+                        i = string_end(text, i)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(i + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            None
+        }
+        _ => Some(at + text[at..].find([',', '}', ']', ' ', '\t', '\n', '\r']).unwrap_or(text.len() - at)),
+    }
+}
+
+/// The known key closest to a misspelt one, if any is close enough
+fn suggest<'a>(key: &str, known: &[&'a str]) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|k| (edit_distance(&key.to_lowercase(), k), *k))
+        .filter(|(distance, k)| *distance <= (k.len() / 3).max(2) && *distance < key.len())
+        .min()
+        .map(|(_, k)| k)
+}
+
+/// Levenshtein distance, in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous[j] + usize::from(ca != *cb);
+            current.push(substitute.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempShastra;
+
+    /// Errors of a config.json, as (line, column, message)
+    fn errors(config: &str) -> Vec<(usize, usize, String)> {
+        let shastra = TempShastra::new("config", &[]);
+        shastra.write(".vyasa/config.json", config);
+        let (_, mut errors) = load_config(&shastra.root);
+        errors.sort_by_key(|e| e.span.start.byte);
+        errors.into_iter().map(|e| (e.span.start.line, e.span.start.column, e.message)).collect()
+    }
+
+    #[test]
+    fn keys_are_found_at_their_own_level() {
+        let config = "{\n  \"comments\": {\"md\": {\"lines\": []}},\n  \"lines\": 3\n}\n";
+        let errors = errors(config);
+        assert_eq!(errors.len(), 2);
+        assert_eq!((errors[0].0, errors[0].1), (2, 23));
+        assert!(errors[0].2.starts_with("unknown key 'lines' - did you mean 'line'?"));
+        assert_eq!((errors[1].0, errors[1].1), (3, 3));
+    }
+
+    #[test]
+    fn nested_type_errors_are_reported_where_the_value_is() {
+        let config = "{\n  \"fmt\": {\n    \"width\": \"wide\"\n  },\n  \"comments\": {\"rs\": {\"block\": 5}}\n}\n";
+        let errors = errors(config);
+        assert_eq!(errors.len(), 2);
+        // serde_json reports the end of the offending value
+        assert_eq!((errors[0].0, errors[0].1), (3, 19));
+        assert_eq!(errors[0].2, "'fmt': invalid type: string \"wide\", expected usize");
+        assert_eq!((errors[1].0, errors[1].1), (5, 32));
+        assert_eq!(errors[1].2, "'comments.rs': invalid type: integer `5`, expected a sequence");
+    }
+
+    #[test]
+    fn invalid_json_is_located() {
+        assert_eq!(errors("{\n  \"name\": \"x\",\n}\n")[0].0, 3);
+    }
+
+    #[test]
+    fn invalid_globs_are_reported_where_they_are_and_left_out() {
+        let config = "{\n  \"include\": [\"docs/**\",\n    \"src/[a\"]\n}\n";
+        let errors = errors(config);
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].0, errors[0].1), (3, 5));
+        assert!(errors[0].2.starts_with("'include': invalid glob 'src/[a'"));
+
+        let shastra = TempShastra::new("globs", &[]);
+        shastra.write(".vyasa/config.json", config);
+        assert_eq!(load_config(&shastra.root).0.include, vec!["docs/**"]);
+        assert_eq!(shastra.parse().config_errors.len(), 1);
+    }
+
+    #[test]
+    fn exclude_adds_to_the_default() {
+        let shastra = TempShastra::new("exclude", &[]);
+        shastra.write(".vyasa/config.json", "{\"exclude\": [\"drafts/**\"]}");
+        assert_eq!(load_config(&shastra.root).0.exclude, vec!["canon.md", "drafts/**"]);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::comment::{BlockComment, CommentSyntax};
+use crate::parser::block_comment_at;
+use similar::TextDiff;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::ops::Range;
+
+/// Replace a byte range of a file - an empty range inserts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub file: String,
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn replace(file: &str, range: Range<usize>, text: impl Into<String>) -> Self {
+        TextEdit { file: file.to_string(), range, text: text.into() }
+    }
+
+    pub fn insert(file: &str, at: usize, text: impl Into<String>) -> Self {
+        TextEdit::replace(file, at..at, text)
+    }
+}
+
+/// A set of edits across files, previewed as a unified diff before it is written
+#[derive(Debug, Default)]
+pub struct Changes {
+    edits: BTreeMap<String, Vec<TextEdit>>,
+}
+
+impl Changes {
+    pub fn push(&mut self, edit: TextEdit) {
+        self.edits.entry(edit.file.clone()).or_default().push(edit);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Every edit, by file
+    pub fn edits(&self) -> impl Iterator<Item = &TextEdit> {
+        self.edits.values().flatten()
+    }
+
+    /// Files touched, sorted
+    pub fn files(&self) -> Vec<&str> {
+        self.edits.keys().map(|f| f.as_str()).collect()
+    }
+
+    /// (file, current content, edited content) for every touched file - a file
+    /// that does not exist yet is empty, so inserts at 0 create it
+    pub fn apply(&self) -> Result<Vec<(String, String, String)>, String> {
+        self.edits
+            .iter()
+            .map(|(file, edits)| {
+                let content = match fs::read_to_string(file) {
+                    Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+                    result => result.map_err(|e| format!("failed to read {}: {}", file, e))?,
+                };
+                let edited = apply_edits(&content, edits).map_err(|e| format!("{}: {}", file, e))?;
+                Ok((file.clone(), content, edited))
+            })
+            .collect()
+    }
+
+    /// Unified diff of every change
+    pub fn diff(&self) -> Result<String, String> {
+        let mut out = String::new();
+        for (file, before, after) in self.apply()? {
+            let diff = TextDiff::from_lines(&before, &after);
+            let a = format!("a/{}", display_path(&file));
+            let b = format!("b/{}", display_path(&file));
+            out.push_str(&diff.unified_diff().context_radius(2).header(&a, &b).to_string());
+        }
+        Ok(out)
+    }
+
+    /// Write every change to disk, returning the number of files written
+    pub fn write(&self) -> Result<usize, String> {
+        let files = self.apply()?;
+        for (file, _, edited) in &files {
+            fs::write(file, edited).map_err(|e| format!("failed to write {}: {}", file, e))?;
+        }
+        Ok(files.len())
+    }
+}
+
+/// Apply edits to content - ranges must be in bounds, on char boundaries and
+/// not overlap (identical edits are applied once)
+pub fn apply_edits(content: &str, edits: &[TextEdit]) -> Result<String, String> {
+    let mut edits: Vec<&TextEdit> = edits.iter().collect();
+    edits.sort_by_key(|e| (e.range.start, e.range.end));
+    edits.dedup();
+
+    let mut out = String::with_capacity(content.len());
+    let mut cursor = 0;
+    for edit in edits {
+        let Range { start, end } = edit.range;
+        if start < cursor || end > content.len() || !content.is_char_boundary(start) || !content.is_char_boundary(end) {
+            return Err(format!("edit at bytes {}..{} does not fit the file - it may have changed", start, end));
+        }
+        out.push_str(&content[cursor..start]);
+        out.push_str(&edit.text);
+        cursor = end;
+    }
+    out.push_str(&content[cursor..]);
+    Ok(out)
+}
+
+/// `**^text^**`
+pub fn mula_marker(text: &str) -> String {
+    format!("**^{}^**", text)
+}
+
+/// `_| text |_` - assembled so vyasa's scan of this source doesn't read it as one
+pub fn anusrit_marker(text: &str) -> String {
+    ["_", "| ", text, " |", "_"].concat()
+}
+
+/// The prefix of a block comment's next line: the continuation (` * `) in
+/// place of the opener, or the prefix itself on a line that doesn't open it
+fn continued(prefix: &str, block: &BlockComment) -> String {
+    let indent = &prefix[..prefix.len() - prefix.trim_start().len()];
+    match block.continuation() {
+        _ if !prefix.trim_start().starts_with(&block.open) => prefix.to_string(),
+        Some(deco) => format!("{} {} ", indent, deco),
+        None => indent.to_string(),
+    }
+}
+
+/// Scanned paths start with `./` when vyasa runs on `.`
+fn display_path(file: &str) -> &str {
+    file.strip_prefix("./").unwrap_or(file)
+}
+
+/// What surrounds the text of a quote line in its file: `// ` before the `>`
+/// in a rust comment, `#[doc = "` before and `"]` after in a doc attribute
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuoteAffixes {
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl QuoteAffixes {
+    /// Affixes of the quote line containing byte `at` of `content`
+    pub fn of_line(content: &str, at: usize) -> Self {
+        let start = content[..at.min(content.len())].rfind('\n').map_or(0, |i| i + 1);
+        let line = content[start..].lines().next().unwrap_or("");
+        let prefix = line.find('>').map_or("", |i| &line[..i]);
+        let doc_attribute = prefix.trim_start().starts_with("#[doc") || prefix.trim_start().starts_with("#![doc");
+        QuoteAffixes {
+            prefix: prefix.to_string(),
+            suffix: if doc_attribute { "\"]".to_string() } else { String::new() },
+        }
+    }
+
+    /// Affixes for lines added to the comment after the quote line at byte
+    /// `at`: its own, but the continuation (` * `) after the line opening a
+    /// block comment. An error if the comment closes on that line - added
+    /// lines would fall outside it.
+    pub fn after_line(content: &str, at: usize, syntax: &CommentSyntax) -> Result<Self, String> {
+        let affixes = QuoteAffixes::of_line(content, at);
+        match block_comment_at(content, at, syntax) {
+            None => Ok(affixes),
+            Some((block, true)) => {
+                Ok(QuoteAffixes { prefix: continued(&affixes.prefix, block), suffix: affixes.suffix })
+            }
+            Some((block, false)) => Err(format!("the quote's comment closes with `{}` on its last line", block.close)),
+        }
+    }
+
+    /// An edit putting `text` on a line of its own before the quote line at
+    /// byte `at` of `file`, in the same comment. On the line opening a block
+    /// comment it goes right after the opener, and the quote moves to a
+    /// continuation line below it.
+    pub fn insert_before(file: &str, content: &str, at: usize, syntax: &CommentSyntax, text: &str) -> TextEdit {
+        let start = content[..at.min(content.len())].rfind('\n').map_or(0, |i| i + 1);
+        let affixes = QuoteAffixes::of_line(content, at);
+        match block_comment_at(content, at, syntax) {
+            Some((block, _)) if affixes.prefix.trim_start().starts_with(&block.open) => TextEdit::insert(
+                file,
+                start + affixes.prefix.len(),
+                format!("{}\n{}", text, continued(&affixes.prefix, block)),
+            ),
+            _ => TextEdit::insert(file, start, affixes.line(text) + "\n"),
+        }
+    }
+
+    /// Affixes for new quote lines in a file with this comment syntax: none in
+    /// prose, the plainest line comment (`// ` rather than `/// `) in code.
+    /// Code without line comments is an error - a quote there would need a
+    /// block comment of its own.
+    pub fn for_syntax(syntax: &CommentSyntax) -> Result<Self, String> {
+        match syntax.line.last() {
+            Some(marker) => Ok(QuoteAffixes { prefix: format!("{} ", marker), suffix: String::new() }),
+            // markdown and html only have `<!-- -->`, and their quotes are prose
+            None if syntax.block.iter().all(|b| b.open == "<!--") => Ok(QuoteAffixes::default()),
+            None => Err("this file type has no line comments to hold a quote".to_string()),
+        }
+    }
+
+    /// One line of text in this comment style
+    pub fn line(&self, text: &str) -> String {
+        if text.is_empty() {
+            // `//` rather than `// `, so no trailing whitespace
+            format!("{}{}", self.prefix.trim_end(), self.suffix)
+        } else {
+            format!("{}{}{}", self.prefix, text, self.suffix)
+        }
+    }
+
+    /// Lines of text in this comment style, joined with newlines (no trailing newline)
+    pub fn lines(&self, lines: &[String]) -> String {
+        lines.iter().map(|l| self.line(l)).collect::<Vec<_>>().join("\n")
+    }
+}
@@ -0,0 +1,231 @@
+use crate::check::{duplicate_bhasyas, Diagnostic, Rule};
+use crate::edit::{Changes, QuoteAffixes, TextEdit};
+use crate::parser::Repository;
+use crate::shastra::ShastraResolver;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Whether a fix is applied by `--fix` alone or needs `--unsafe` too
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Safety {
+    /// Only the flagged text changes, and nothing it means
+    Safe,
+    /// The edit reaches beyond the flagged text - other mantras of the bhasya,
+    /// or the layout of a wrapped line
+    Unsafe,
+}
+
+/// A mechanical fix for one diagnostic
+#[derive(Debug, Clone)]
+pub struct Fix {
+    pub rule: Rule,
+    pub file: String,
+    pub line: usize,
+    pub safety: Safety,
+    /// What the fix does - "spell as ^m^"
+    pub description: String,
+    pub edits: Vec<TextEdit>,
+}
+
+/// Fixes for the diagnostics `check` found in `repo`, in their order.
+/// `shastra` picks the observed shastra ambiguous anusrits resolve to - they
+/// get no fix without it, nor where that shastra doesn't define the mantra.
+pub fn plan(repo: &Repository, diagnostics: &[Diagnostic], shastra: Option<&str>) -> Result<Vec<Fix>, String> {
+    let mut contents: HashMap<String, String> = HashMap::new();
+    let mut resolver = ShastraResolver::new(repo);
+    if let Some(shastra) = shastra {
+        resolver.resolve(shastra).map_err(|e| e.to_string())?;
+    }
+    let duplicates = duplicate_bhasyas(repo);
+    let mut fixes = Vec::new();
+
+    for diagnostic in diagnostics {
+        let (Some(file), Some(span)) = (&diagnostic.file, diagnostic.span) else {
+            continue;
+        };
+        if !contents.contains_key(file) {
+            let content = fs::read_to_string(file).map_err(|e| format!("failed to read {}: {}", file, e))?;
+            contents.insert(file.clone(), content);
+        }
+        let content = &contents[file];
+        let fix = |safety, description, edits| Fix {
+            rule: diagnostic.rule,
+            file: file.clone(),
+            line: span.start.line,
+            safety,
+            description,
+            edits,
+        };
+
+        match diagnostic.rule {
+            // _| check fixes non-canonical spellings |_
+            Rule::NonCanonicalSpelling => {
+                let Some(spelling) = repo.non_canonical.iter().find(|s| &s.file == file && s.span == span) else {
+                    continue;
+                };
+                // the text between the delimiters, keeping the delimiters as written
+                let marker = &content[span.bytes()];
+                let inner = if let Some(rest) = marker.strip_prefix("**^") {
+                    let end = rest.find('^').map_or(marker.len(), |i| 3 + i);
+                    span.start.byte + 3..span.start.byte + end
+                } else {
+                    span.start.byte + 2..span.end.byte - 2
+                };
+                let (text, canonical) = (&content[inner.clone()], &spelling.canonical);
+                let lead = &text[..text.len() - text.trim_start().len()];
+                let trail = &text[text.trim_end().len()..];
+                // a wrapped mantra is joined onto one line
+                let safety = if span.start.line == span.end.line { Safety::Safe } else { Safety::Unsafe };
+                fixes.push(fix(
+                    safety,
+                    format!("spell as ^{}^", canonical),
+                    vec![TextEdit::replace(file, inner, format!("{}{}{}", lead, canonical, trail))],
+                ));
+            }
+            // _| check fixes duplicate bhasyas as uddhrit |_
+            Rule::DuplicateBhasya => {
+                let Some(name) = repo.config.name.as_deref() else {
+                    continue;
+                };
+                let Some((_, copies)) = duplicates.iter().find(|(_, c)| &c[0].file == file && c[0].span == span)
+                else {
+                    continue;
+                };
+                for copy in &copies[1..] {
+                    if !contents.contains_key(&copy.file) {
+                        let content =
+                            fs::read_to_string(&copy.file).map_err(|e| format!("failed to read {}: {}", copy.file, e))?;
+                        contents.insert(copy.file.clone(), content);
+                    }
+                    let syntax = repo.config.comment_syntax(Path::new(&copy.file));
+                    let line = format!("shastra: {}", name);
+                    let content = &contents[&copy.file];
+                    let edit = QuoteAffixes::insert_before(&copy.file, content, copy.span.start.byte, &syntax, &line);
+                    // quoting the copy quotes every mantra it defines, not only this one
+                    let safety = if copy.mulas.len() == 1 { Safety::Safe } else { Safety::Unsafe };
+                    fixes.push(Fix {
+                        rule: diagnostic.rule,
+                        file: copy.file.clone(),
+                        line: copy.line,
+                        safety,
+                        description: format!("quote from {}:{} with `shastra: {}`", file, span.start.line, name),
+                        edits: vec![edit],
+                    });
+                }
+            }
+            Rule::AmbiguousAnusrit => {
+                let Some(shastra) = shastra else {
+                    continue;
+                };
+                let Some(anusrit) = repo.anusrits.iter().find(|a| &a.file == file && a.span == span) else {
+                    continue;
+                };
+                if !resolver.resolve(shastra).is_ok_and(|external| external.is_live(&anusrit.mantra_text)) {
+                    continue;
+                }
+                fixes.push(fix(
+                    Safety::Safe,
+                    format!("resolve to @{}", shastra),
+                    vec![TextEdit::insert(file, anusrit.span.end.byte, format!("@{}", shastra))],
+                ));
+            }
+            _ => {}
+        }
+    }
+    // a bhasya copying several mantras is a duplicate for each of them, with one fix
+    let mut unique: Vec<Fix> = Vec::with_capacity(fixes.len());
+    for fix in fixes {
+        if !unique.iter().any(|f| f.edits == fix.edits) {
+            unique.push(fix);
+        }
+    }
+    Ok(unique)
+}
+
+/// The edits of `fixes`, unsafe ones only with `include_unsafe`
+pub fn changes(fixes: &[Fix], include_unsafe: bool) -> Changes {
+    let mut changes = Changes::default();
+    for fix in fixes {
+        if fix.safety == Safety::Safe || include_unsafe {
+            for edit in &fix.edits {
+                changes.push(edit.clone());
+            }
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::check::check;
+    use crate::edit::{anusrit_marker, mula_marker};
+    use crate::parser::BhasyaKind;
+    use crate::testing::TempShastra;
+
+    #[test]
+    fn spellings_are_fixed_keeping_their_delimiters() {
+        let marker = mula_marker("wrapped  mantra text");
+        let (first, second) = marker.split_at(marker.find(" text").unwrap());
+        let notes = format!(
+            "> {} - spaced.\n\n> {}\n>{} - wrapped.\n\nsee {}\n",
+            mula_marker("two  spaces"),
+            first,
+            second,
+            anusrit_marker("two\u{a0}spaces")
+        );
+        let shastra = TempShastra::new("spelling", &[("notes.md", &notes)]);
+        let repo = shastra.parse();
+        let fixes = plan(&repo, &check(&repo), None).unwrap();
+        let safety: Vec<_> = fixes.iter().map(|f| (f.line, f.safety)).collect();
+        assert_eq!(safety, vec![(1, Safety::Safe), (3, Safety::Unsafe), (6, Safety::Safe)]);
+
+        changes(&fixes, false).write().unwrap();
+        let fixed = notes.replace("two  spaces", "two spaces").replace("two\u{a0}spaces", "two spaces");
+        assert_eq!(shastra.read("notes.md"), fixed);
+        let repo = shastra.parse();
+        changes(&plan(&repo, &check(&repo), None).unwrap(), true).write().unwrap();
+        // the unsafe fix joins the wrapped mantra onto one line
+        assert!(shastra.read("notes.md").contains(&format!("> {} - wrapped.", mula_marker("wrapped mantra text"))));
+    }
+
+    #[test]
+    fn ambiguous_anusrits_resolve_to_the_chosen_shastra() {
+        let defines = |m: &str| format!("> {} - defined here.\n", mula_marker(m));
+        let physics = TempShastra::new("physics", &[("notes.md", &(defines("energy") + "\n" + &defines("mass")))]);
+        let chemistry = TempShastra::new("chemistry", &[("notes.md", &defines("energy"))]);
+        let ours = TempShastra::new("ours", &[("code.rs", &format!("// {}\n", anusrit_marker("energy")))]);
+        ours.write(
+            ".vyasa/shastra.json",
+            &serde_json::json!({
+                "physics": physics.root.to_string_lossy(),
+                "chemistry": chemistry.root.to_string_lossy(),
+            })
+            .to_string(),
+        );
+        let repo = ours.parse();
+        let diagnostics = check(&repo);
+        assert!(plan(&repo, &diagnostics, None).unwrap().is_empty());
+        assert!(plan(&repo, &diagnostics, Some("biology")).is_err());
+
+        changes(&plan(&repo, &diagnostics, Some("chemistry")).unwrap(), false).write().unwrap();
+        assert_eq!(ours.read("code.rs"), format!("// {}@chemistry\n", anusrit_marker("energy")));
+    }
+
+    #[test]
+    fn duplicate_in_a_block_comment_is_quoted_inside_it() {
+        let bhasya = format!("> {} - said twice.", mula_marker("twice"));
+        let shastra = TempShastra::new(
+            "dup",
+            &[("a.md", &format!("{}\n", bhasya)), ("b.rs", &format!("/* {}\n */\nfn f() {{}}\n", bhasya))],
+        );
+        let repo = shastra.parse();
+        let fixes = plan(&repo, &check(&repo), None).unwrap();
+        changes(&fixes, true).write().unwrap();
+
+        assert_eq!(shastra.read("b.rs"), format!("/* shastra: dup\n * {}\n */\nfn f() {{}}\n", bhasya));
+        let repo = shastra.parse();
+        assert!(repo.bhasyas.iter().any(|b| matches!(&b.kind, BhasyaKind::Uddhrit(name) if name == "dup")));
+    }
+}
@@ -0,0 +1,324 @@
+use crate::config::FmtConfig;
+use crate::edit::{Changes, QuoteAffixes, TextEdit};
+use crate::parser::{Bhasya, BhasyaKind, Repository};
+use std::collections::HashMap;
+use std::fs;
+
+/// The edits that format every bhasya, and the bhasyas left alone
+#[derive(Debug, Default)]
+pub struct FmtPlan {
+    pub changes: Changes,
+    /// Bhasyas rewritten
+    pub formatted: usize,
+    /// Bhasyas vyasa can't rewrite safely - (file, line, why)
+    pub skipped: Vec<(String, usize, &'static str)>,
+}
+
+/// Plan formatting every bhasya of `repo` except uddhrits, which stay
+/// verbatim copies of their source
+pub fn plan(repo: &Repository) -> Result<FmtPlan, String> {
+    let mut plan = FmtPlan::default();
+    let mut contents: HashMap<&str, String> = HashMap::new();
+    for bhasya in &repo.bhasyas {
+        if matches!(bhasya.kind, BhasyaKind::Uddhrit(_)) {
+            continue;
+        }
+        if !contents.contains_key(bhasya.file.as_str()) {
+            let content =
+                fs::read_to_string(&bhasya.file).map_err(|e| format!("failed to read {}: {}", bhasya.file, e))?;
+            contents.insert(&bhasya.file, content);
+        }
+        match format_bhasya(bhasya, &contents[bhasya.file.as_str()], &repo.config.fmt) {
+            Ok(Some(text)) => {
+                plan.changes.push(TextEdit::replace(&bhasya.file, bhasya.span.bytes(), text));
+                plan.formatted += 1;
+            }
+            Ok(None) => {}
+            Err(why) => plan.skipped.push((bhasya.file.clone(), bhasya.line, why)),
+        }
+    }
+    Ok(plan)
+}
+
+// _| vyasa fmt never changes what a bhasya means |_
+/// The formatted quote block of `bhasya` in `content`, None if it is already
+/// formatted, or why it can't be formatted safely
+pub fn format_bhasya(bhasya: &Bhasya, content: &str, config: &FmtConfig) -> Result<Option<String>, &'static str> {
+    let original = &content[bhasya.span.bytes()];
+    if original.contains('\r') {
+        return Err("CRLF line endings");
+    }
+    let affixes = QuoteAffixes::of_line(content, bhasya.span.start.byte);
+
+    // every line must be exactly affixes + `>` + the parsed text, or
+    // rewriting the block would lose something (a `*/`, a stray marker)
+    let lines: Vec<&str> = bhasya.paragraph.lines().collect();
+    let original_lines: Vec<&str> = original.split('\n').collect();
+    if lines.len() != original_lines.len() {
+        return Err("lines don't match the parsed bhasya");
+    }
+    for (line, text) in original_lines.iter().zip(&lines) {
+        let quoted = line
+            .trim_end()
+            .strip_suffix(affixes.suffix.as_str())
+            .and_then(|l| l.strip_prefix(affixes.prefix.as_str()))
+            .and_then(|l| l.strip_prefix('>'));
+        if quoted.is_none_or(|q| q.trim() != text.trim()) {
+            return Err("comment markers differ between lines");
+        }
+    }
+    if lines.iter().any(|l| is_structure(l.trim())) {
+        return Err("code, table, heading or nested quote inside");
+    }
+
+    let segments = segments(&lines, &config.separator);
+    let marker_width = affixes.prefix.chars().count() + affixes.suffix.chars().count() + 2;
+    let width = config.width.saturating_sub(marker_width).max(20);
+
+    let mut out = Vec::new();
+    for segment in &segments {
+        match segment {
+            Segment::Blank => out.push(affixes.line(">")),
+            Segment::Text(text) => {
+                out.extend(wrap(text, width).into_iter().map(|l| affixes.line(&format!("> {}", l))));
+            }
+        }
+    }
+    let formatted = out.join("\n");
+    Ok((formatted != original).then_some(formatted))
+}
+
+/// A run of quote lines that reflow together, or a blank `>` line between them
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Text(String),
+    Blank,
+}
+
+/// Split a bhasya's lines into segments: each mula marker and each list item
+/// starts one, blank lines separate them. The first mula segment is moved to
+/// the top, so the block opens with its mantra, and a marker alone on its
+/// line is joined with the `- commentary` below it.
+fn segments(lines: &[&str], separator: &str) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut mulas = Vec::new();
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() {
+            if segments.last().is_some_and(|s| *s != Segment::Blank) {
+                segments.push(Segment::Blank);
+            }
+        } else if line.starts_with("**^") {
+            mulas.push(segments.len());
+            segments.push(Segment::Text(line.to_string()));
+        } else if is_list_item(line) {
+            match segments.last_mut() {
+                // `**^m^**` alone, its commentary on the next line after a `-`
+                Some(Segment::Text(text)) if text.starts_with("**^") && atom_len(text) == text.len() => {
+                    text.push(' ');
+                    text.push_str(line);
+                }
+                _ => segments.push(Segment::Text(line.to_string())),
+            }
+        } else if let Some(Segment::Text(text)) = segments.last_mut() {
+            text.push(' ');
+            text.push_str(line);
+        } else {
+            segments.push(Segment::Text(line.to_string()));
+        }
+    }
+    // only once the segment is whole - a wrapped line can end in its separator
+    for &index in &mulas {
+        if let Segment::Text(text) = &mut segments[index] {
+            *text = with_separator(text, separator);
+        }
+    }
+    if let Some(index) = mulas.first().copied().filter(|&i| i > 0) {
+        // the text before it becomes a paragraph of its own after it
+        let mula = segments.remove(index);
+        segments.splice(0..0, [mula, Segment::Blank]);
+        segments.dedup_by(|a, b| *a == Segment::Blank && *b == Segment::Blank);
+    }
+    while segments.last() == Some(&Segment::Blank) {
+        segments.pop();
+    }
+    segments
+}
+
+/// `**^mantra^** - commentary` with the configured separator, when the line
+/// separates its marker from the commentary with `-`, `—`, `–` or `:`
+fn with_separator(line: &str, separator: &str) -> String {
+    let marker_len = atom_len(line);
+    let (marker, rest) = line.split_at(marker_len);
+    let trimmed = rest.trim_start();
+    match trimmed.strip_prefix(['-', '—', '–', ':']) {
+        // `**^m^**-based` is one word, not a separator
+        Some(commentary) if commentary.starts_with(char::is_whitespace) => {
+            format!("{}{}{}", marker, separator, commentary.trim_start())
+        }
+        _ => line.to_string(),
+    }
+}
+
+/// Greedily fill lines up to `width`, never breaking inside a marker and
+/// never starting a line with a word that would change its meaning there -
+/// such a word takes the words before it to the next line instead
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let length = |line: &[&str]| line.iter().map(|w| w.chars().count()).sum::<usize>() + line.len().saturating_sub(1);
+    let mut lines: Vec<Vec<&str>> = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    for word in words(text) {
+        if current.is_empty() || length(&current) + 1 + word.chars().count() <= width {
+            current.push(word);
+            continue;
+        }
+        // the new line starts at the last word that can start one (never the first)
+        let mut next = vec![word];
+        let mut split = current.len();
+        while split > 1 && !can_start_line(next[0]) {
+            split -= 1;
+            next.insert(0, current[split]);
+        }
+        if can_start_line(next[0]) {
+            current.truncate(split);
+            lines.push(std::mem::replace(&mut current, next));
+        } else {
+            current.push(word);
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines.into_iter().map(|line| line.join(" ")).collect()
+}
+
+/// Split on whitespace, keeping mula markers, anusrits and code spans whole
+fn words(text: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut pos = 0;
+    while pos < text.len() {
+        let rest = &text[pos..];
+        if let Some(c) = rest.chars().next().filter(|c| c.is_whitespace()) {
+            pos += c.len_utf8();
+            continue;
+        }
+        let start = pos;
+        while pos < text.len() && !text[pos..].starts_with(char::is_whitespace) {
+            pos += atom_len(&text[pos..]);
+        }
+        words.push(&text[start..pos]);
+    }
+    words
+}
+
+/// Length of the unbreakable unit at the start of `text`: a whole mula
+/// marker, anusrit or code span - or else one character
+fn atom_len(text: &str) -> usize {
+    if let Some(inner) = text.strip_prefix("**^") {
+        if let Some(end) = inner.find('^') {
+            let len = 3 + end + 1;
+            return len + text[len..].chars().take(2).take_while(|&c| c == '*').count();
+        }
+    }
+    // an anusrit, its opener matched char by char so this source doesn't contain one
+    if text.starts_with('_') && text[1..].starts_with('|') {
+        if let Some(end) = text[2..].find("|_") {
+            return 2 + end + 2;
+        }
+    }
+    if let Some(code) = text.strip_prefix('`') {
+        if let Some(end) = code.find('`') {
+            return end + 2;
+        }
+    }
+    text.chars().next().map_or(1, char::len_utf8)
+}
+
+/// Lines vyasa leaves alone rather than reflow: code fences, tables, headings,
+/// html and nested quotes
+fn is_structure(line: &str) -> bool {
+    ["```", "~~~", "|", "#", "<", ">"].iter().any(|p| line.starts_with(p))
+}
+
+/// `- item`, `* item`, `+ item`, `1. item` or `1) item`
+fn is_list_item(line: &str) -> bool {
+    line.split_once(' ').is_some_and(|(marker, _)| is_list_marker(marker))
+}
+
+fn is_list_marker(word: &str) -> bool {
+    matches!(word, "-" | "*" | "+")
+        || word
+            .strip_suffix(['.', ')'])
+            .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Whether a word keeps its meaning at the start of a quote line - a mula
+/// marker would become a definition, a `-` a list item, a `#` a heading -
+/// and isn't a dash, which stays with the word before it (so a separator
+/// stays with its marker)
+fn can_start_line(word: &str) -> bool {
+    !(word.starts_with("**^") || is_list_marker(word) || is_structure(word) || matches!(word, "—" | "–"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::mula_marker;
+    use crate::testing::TempShastra;
+
+    fn text(s: &str) -> Segment {
+        Segment::Text(s.to_string())
+    }
+
+    #[test]
+    fn separator_at_the_end_of_a_wrapped_line_is_kept() {
+        let lines = ["**^m^** -", "the commentary"];
+        assert_eq!(segments(&lines, " - "), vec![text("**^m^** - the commentary")]);
+        let lines = ["**^m^**", "- the commentary"];
+        assert_eq!(segments(&lines, " - "), vec![text("**^m^** - the commentary")]);
+        let lines = ["**^m^**: the commentary"];
+        assert_eq!(segments(&lines, " - "), vec![text("**^m^** - the commentary")]);
+    }
+
+    #[test]
+    fn mula_moves_to_the_top() {
+        let lines = ["some context first", "**^m^** - the commentary"];
+        let expected = vec![text("**^m^** - the commentary"), Segment::Blank, text("some context first")];
+        assert_eq!(segments(&lines, " - "), expected);
+    }
+
+    #[test]
+    fn wrap_never_starts_a_line_with_a_marker_or_dash() {
+        // a mula marker starting a line would become a definition
+        assert_eq!(wrap("a **^b c^** d", 5), vec!["a **^b c^**", "d"]);
+        assert_eq!(wrap("aaaa — bbbb", 6), vec!["aaaa —", "bbbb"]);
+    }
+
+    #[test]
+    fn formatting_twice_changes_nothing_the_second_time() {
+        let long = mula_marker("a mantra long enough that its separator lands right at the edge");
+        let notes = [
+            format!("> {} - followed by commentary that has to wrap onto the next line somewhere.", long),
+            String::new(),
+            format!("> {}", mula_marker("alone")),
+            "> - with its commentary below".to_string(),
+            String::new(),
+            "> context before the mantra.".to_string(),
+            format!("> {}: and then its commentary.", mula_marker("later")),
+        ];
+        let code =
+            format!("// > {} -   spaced   commentary\n// > over two lines\nfn main() {{}}\n", mula_marker("in code"));
+        let shastra = TempShastra::new("fmt", &[("notes.md", &(notes.join("\n") + "\n")), ("code.rs", &code)]);
+
+        let first = plan(&shastra.parse()).unwrap();
+        assert_eq!(first.formatted, 4);
+        first.changes.write().unwrap();
+        let formatted = shastra.read("notes.md");
+        assert!(formatted.starts_with(&format!("> {} -\n", long)), "{}", formatted);
+        let code = format!("// > {} - spaced commentary over two lines\nfn main() {{}}\n", mula_marker("in code"));
+        assert_eq!(shastra.read("code.rs"), code);
+
+        let second = plan(&shastra.parse()).unwrap();
+        assert_eq!(second.formatted, 0, "{}", second.changes.diff().unwrap());
+    }
+}
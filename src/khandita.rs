@@ -0,0 +1,145 @@
+use crate::edit::Changes;
+use crate::parser::{normalize_mantra, BhasyaKind, Repository, Span, TyaktaNote};
+use crate::quote::{append_block, quote_lines, source_bhasya};
+use crate::shastra::ShastraResolver;
+use std::path::Path;
+
+/// A khandita block scaffolded from an observed shastra's bhasya
+#[derive(Debug, Default)]
+pub struct RefutePlan {
+    pub shastra: String,
+    pub mantra: String,
+    /// The block, `khandita:` line first, without comment markers
+    pub lines: Vec<String>,
+    /// Appending the block to the target file, if one was given
+    pub changes: Changes,
+}
+
+// _| vyasa refute scaffolds a khandita |_
+/// Plan a `khandita:` block refuting `mantra` from observed shastra `alias`,
+/// starting with the source's mantra line for the refutation to follow.
+/// Bhasyas the source has already abandoned can still be refuted.
+pub fn plan(repo: &Repository, alias: &str, mantra: &str, into: Option<&Path>) -> Result<RefutePlan, String> {
+    let mantra = normalize_mantra(mantra);
+    let already = repo.bhasyas.iter().find(|b| {
+        matches!(&b.kind, BhasyaKind::Khandita(s) if s == alias) && b.mulas.iter().any(|m| m.mantra_text == mantra)
+    });
+    if let Some(bhasya) = already {
+        return Err(format!("^{}^ is already refuted from '{}' at {}:{}", mantra, alias, bhasya.file, bhasya.line));
+    }
+
+    let mut resolver = ShastraResolver::new(repo);
+    let external = resolver.resolve(alias).map_err(|e| e.to_string())?;
+    let bhasya = source_bhasya(external, alias, &mantra, true)?;
+
+    // the lines holding the mula marker - it may wrap
+    let mut lines = vec![format!("khandita: {}", alias)];
+    if let Some(mula) = bhasya.mulas.iter().find(|m| m.mantra_text == mantra) {
+        let first = mula.span.start.line - bhasya.span.start.line;
+        let last = mula.span.end.line - bhasya.span.start.line;
+        lines.extend(quote_lines(bhasya).into_iter().skip(first).take(last - first + 1));
+    }
+
+    let mut plan = RefutePlan { shastra: alias.to_string(), mantra, lines, changes: Changes::default() };
+    if let Some(into) = into {
+        plan.changes.push(append_block(repo, into, &plan.lines)?);
+    }
+    Ok(plan)
+}
+
+/// Where a refutation stands in the shastra it refutes
+#[derive(Debug, Clone, PartialEq)]
+pub enum KhanditaStatus {
+    /// The source still teaches the bhasya
+    Pending,
+    /// The source has abandoned the bhasya - its `tyakta:` line says when and for what
+    Resolved(TyaktaNote),
+    /// The source has no such bhasya (check reports this)
+    Missing,
+    /// The source shastra could not be resolved
+    Unresolved(String),
+}
+
+/// One `khandita:` refutation we have issued
+#[derive(Debug, Clone)]
+pub struct Khandita {
+    pub shastra: String,
+    pub mantra: String,
+    pub file: String,
+    pub span: Span,
+    pub status: KhanditaStatus,
+}
+
+// _| khandita expects the source to tyakta |_
+/// Every mantra we refute, in source order, with whether its source has
+/// since abandoned it
+pub fn status(repo: &Repository) -> Vec<Khandita> {
+    let mut resolver = ShastraResolver::new(repo);
+    let mut khanditas = Vec::new();
+    for (mantra, bhasya) in repo.mula_mantras_with_bhasyas() {
+        let BhasyaKind::Khandita(shastra) = &bhasya.kind else {
+            continue;
+        };
+        let status = match resolver.resolve(shastra) {
+            Ok(external) if external.is_live(mantra) => KhanditaStatus::Pending,
+            Ok(external) => match external.tyakta_note(mantra) {
+                Some(note) => KhanditaStatus::Resolved(note.clone()),
+                None => KhanditaStatus::Missing,
+            },
+            Err(e) => KhanditaStatus::Unresolved(e.to_string()),
+        };
+        khanditas.push(Khandita {
+            shastra: shastra.clone(),
+            mantra: mantra.to_string(),
+            file: bhasya.file.clone(),
+            span: bhasya.span,
+            status,
+        });
+    }
+    khanditas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::mula_marker;
+    use crate::testing::TempShastra;
+
+    #[test]
+    fn refutation_starts_from_the_lines_of_the_mula_marker() {
+        let marker = mula_marker("energy is conserved");
+        let (first, second) = marker.split_at(marker.find(" conserved").unwrap());
+        let physics = format!("> {}\n>{} - always,\n> in every system.\n", first, second);
+        let physics = TempShastra::new("physics", &[("notes.md", &physics)]);
+        let ours = TempShastra::new("ours", &[("notes.md", "# refutations\n")]);
+        ours.observe("physics", &physics);
+
+        let refute = plan(&ours.parse(), "physics", "energy is conserved", Some(&ours.path("notes.md"))).unwrap();
+        let lines = vec!["khandita: physics".to_string(), format!("> {}", first), format!(">{} - always,", second)];
+        assert_eq!(refute.lines, lines);
+        refute.changes.write().unwrap();
+        assert_eq!(ours.read("notes.md"), format!("# refutations\n\n{}\n", lines.join("\n")));
+
+        let error = plan(&ours.parse(), "physics", "energy is conserved", None).unwrap_err();
+        assert!(error.contains("already refuted"), "{}", error);
+    }
+
+    #[test]
+    fn status_follows_the_source() {
+        let taught = format!("> {} - taught.\n", mula_marker("ether"));
+        let physics = TempShastra::new("physics", &[("notes.md", &taught)]);
+        let refutations =
+            ["ether", "phlogiston"].map(|m| format!("khandita: physics\n> {} - wrong.\n", mula_marker(m)));
+        let ours = TempShastra::new("ours", &[("notes.md", &refutations.join("\n"))]);
+        ours.observe("physics", &physics);
+        let statuses = |ours: &TempShastra| status(&ours.parse()).into_iter().map(|k| k.status).collect::<Vec<_>>();
+        assert_eq!(statuses(&ours), vec![KhanditaStatus::Pending, KhanditaStatus::Missing]);
+
+        physics.write("notes.md", &format!("tyakta: 2024-05-01\n{}", taught));
+        let note = TyaktaNote { date: chrono::NaiveDate::from_ymd_opt(2024, 5, 1), ..TyaktaNote::default() };
+        assert_eq!(statuses(&ours)[0], KhanditaStatus::Resolved(note));
+
+        ours.write(".vyasa/shastra.json", "{}");
+        assert!(matches!(&statuses(&ours)[0], KhanditaStatus::Unresolved(e) if e.contains("undefined shastra")));
+    }
+}
@@ -0,0 +1,59 @@
+//! vyasa - organize and curate knowledge through mantras
+//!
+//! The `vyasa` binary is a thin consumer of this library. Tools that want to
+//! read a shastra without scraping CLI output use it directly:
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! let repo = vyasa::Repository::parse(Path::new(".")).unwrap();
+//! for diagnostic in vyasa::check::check(&repo) {
+//!     println!("{:?}: {}", diagnostic.rule, diagnostic.message);
+//! }
+//! ```
+//!
+//! - [`parser`] - parsing entry points ([`Repository::parse`], [`parse_file`])
+//!   and the model types (bhasyas, mulas, anusrits, spans)
+//! - [`check`] - the rules `vyasa check` enforces, as [`check::Diagnostic`]s
+//! - [`fix`] - mechanical fixes for some of them, safe or unsafe
+//! - [`shastra`] - resolving the shastras a repository observes
+//! - [`config`] - loading and validating the `.vyasa` config files
+//! - [`comment`] - comment syntax per file type
+//! - [`rename`] - rewording a mantra everywhere it is used
+//! - [`tyakta`] - deprecating a mantra and finding what it breaks
+//! - [`quote`] - quoting a bhasya from an observed shastra
+//! - [`khandita`] - refuting one, and tracking whether the source abandons it
+//! - [`edit`] - byte-range edits to source files, previewed as diffs
+//! - [`canon`] - the `canon.md` digest of a shastra's bhasyas
+//! - [`fmt`] - laying out bhasyas consistently
+//! - [`render`] - a shastra as linked html pages
+//! - [`lsp`] - the language server editors talk to
+//! - [`watch`] - re-checking a shastra as its files change
+//! - [`cache`] - the on-disk parse cache in `.vyasa/cache/`
+
+pub mod cache;
+pub mod canon;
+pub mod check;
+pub mod comment;
+pub mod config;
+pub mod edit;
+pub mod fix;
+pub mod fmt;
+pub mod khandita;
+pub mod lsp;
+pub mod parser;
+pub mod quote;
+pub mod rename;
+pub mod render;
+pub mod shastra;
+#[cfg(test)]
+mod testing;
+pub mod tyakta;
+pub mod watch;
+
+pub use check::{Diagnostic, Rule};
+pub use parser::{
+    normalize_mantra, parse_file, Anusrit, Bhasya, BhasyaKind, Config, MantraInfo, Mula, ParsedFile,
+    Position, Repository, Span, TyaktaNote,
+};
+pub use shastra::{ResolveError, ShastraResolver};
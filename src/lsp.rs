@@ -0,0 +1,736 @@
+use crate::check::{check_with, Diagnostic as CheckDiagnostic};
+use crate::edit::anusrit_marker;
+use crate::parser::{parse_file, Anusrit, BhasyaKind, Repository, Span};
+use crate::quote::relative_to;
+use crate::rename;
+use crate::shastra::ShastraResolver;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, References, Rename, Request as _};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse, CompletionTextEdit,
+    Diagnostic, DiagnosticSeverity, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, InitializeParams, Location, MarkupContent, MarkupKind, NumberOrString, OneOf, Position,
+    PublishDiagnosticsParams, Range, ReferenceParams, RenameParams, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextEdit, Url, WorkspaceEdit,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The opening anusrit delimiter, assembled so vyasa's scan of this source
+/// doesn't read an anusrit here
+const ANUSRIT_OPEN: &str = concat!("_", "|");
+
+/// Serve the shastra at `path` (or the workspace the client opens) over
+/// `connection` until the client shuts the server down
+pub fn serve(connection: &Connection, path: &Path) -> Result<(), String> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::INCREMENTAL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["|".to_string()]),
+            ..CompletionOptions::default()
+        }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    let capabilities = serde_json::to_value(capabilities).map_err(|e| e.to_string())?;
+    let params = connection.initialize(capabilities).map_err(|e| e.to_string())?;
+    let params: InitializeParams = serde_json::from_value(params).map_err(|e| e.to_string())?;
+
+    let root = workspace_root(&params).unwrap_or_else(|| path.to_path_buf());
+    let mut server = Server::new(&root)?;
+    server.publish_diagnostics(connection)?;
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request).map_err(|e| e.to_string())? {
+                    return Ok(());
+                }
+                let response = server.handle_request(request);
+                connection.sender.send(Message::Response(response)).map_err(|e| e.to_string())?;
+            }
+            Message::Notification(notification) => {
+                if server.handle_notification(notification)? {
+                    server.publish_diagnostics(connection)?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// The first workspace folder the client opened, if it is a local folder
+fn workspace_root(params: &InitializeParams) -> Option<PathBuf> {
+    #[allow(deprecated)]
+    let root_uri = params.root_uri.as_ref();
+    let uri = params.workspace_folders.as_ref().and_then(|f| f.first()).map(|f| &f.uri).or(root_uri)?;
+    uri.to_file_path().ok()
+}
+
+/// The shastra as the editor sees it - files on disk, with open documents
+/// in place of their saved content
+struct Server {
+    root: PathBuf,
+    repo: Repository,
+    resolver: ShastraResolver,
+    /// Text of open documents, by file name
+    documents: HashMap<String, String>,
+    /// Files diagnostics were last published for, to clear the fixed ones
+    published: HashSet<String>,
+}
+
+/// A mantra under the cursor, and the shastra it is looked up in - None for
+/// an anusrit without `@shastra`, which resolves here or in one observed shastra
+struct Target {
+    mantra: String,
+    shastra: Option<String>,
+}
+
+/// A bhasya defining a mantra, copied out of the shastra that holds it
+struct Definition {
+    /// Alias of the observed shastra, None for our own
+    shastra: Option<String>,
+    file: String,
+    /// The file relative to the root of its shastra
+    source: String,
+    line: usize,
+    /// Span of the mula marker
+    mula: Span,
+    paragraph: String,
+    kind: BhasyaKind,
+}
+
+impl Server {
+    fn new(root: &Path) -> Result<Self, String> {
+        // absolute file names, so they map to and from file uris
+        let root = root.canonicalize().map_err(|e| format!("can't open {}: {}", root.display(), e))?;
+        let repo = Repository::parse(&root)?;
+        let resolver = ShastraResolver::new(&repo);
+        Ok(Server { root, repo, resolver, documents: HashMap::new(), published: HashSet::new() })
+    }
+
+    fn handle_request(&mut self, request: Request) -> Response {
+        match request.method.as_str() {
+            Completion::METHOD => respond(request, |params| self.completion(params)),
+            HoverRequest::METHOD => respond(request, |params| self.hover(params)),
+            GotoDefinition::METHOD => respond(request, |params| self.definition(params)),
+            References::METHOD => respond(request, |params| self.references(params)),
+            Rename::METHOD => respond(request, |params| self.rename(params)),
+            method => {
+                let message = format!("unknown request {}", method);
+                Response::new_err(request.id, ErrorCode::MethodNotFound as i32, message)
+            }
+        }
+    }
+
+    /// Apply a document notification, returning whether the shastra changed
+    fn handle_notification(&mut self, notification: Notification) -> Result<bool, String> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: lsp_types::DidOpenTextDocumentParams = extract(notification)?;
+                let Some(file) = self.file_name(&params.text_document.uri) else {
+                    return Ok(false);
+                };
+                self.documents.insert(file.clone(), params.text_document.text);
+                Ok(self.reparse(&file))
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: lsp_types::DidChangeTextDocumentParams = extract(notification)?;
+                let Some(file) = self.file_name(&params.text_document.uri) else {
+                    return Ok(false);
+                };
+                let Some(text) = self.documents.get_mut(&file) else {
+                    return Ok(false);
+                };
+                for change in params.content_changes {
+                    match change.range {
+                        Some(range) => {
+                            let (start, end) = (byte_at(text, range.start), byte_at(text, range.end));
+                            text.replace_range(start..end.max(start), &change.text);
+                        }
+                        None => *text = change.text,
+                    }
+                }
+                Ok(self.reparse(&file))
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: lsp_types::DidCloseTextDocumentParams = extract(notification)?;
+                let Some(file) = self.file_name(&params.text_document.uri) else {
+                    return Ok(false);
+                };
+                // back to what is on disk - unsaved edits are gone
+                self.documents.remove(&file);
+                Ok(self.reparse(&file))
+            }
+            DidSaveTextDocument::METHOD => {
+                let params: lsp_types::DidSaveTextDocumentParams = extract(notification)?;
+                let uri = &params.text_document.uri;
+                if self.file_name(uri).is_some() {
+                    let is_config = uri.path().contains("/.vyasa/");
+                    if is_config {
+                        self.reload()?;
+                    }
+                    return Ok(is_config);
+                }
+                // a file of an observed shastra - it is parsed again when next used
+                Ok(uri.to_file_path().is_ok_and(|path| self.resolver.forget(&path)))
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Parse the whole shastra again - after its config changed - keeping open documents
+    fn reload(&mut self) -> Result<(), String> {
+        self.repo = Repository::parse(&self.root)?;
+        self.resolver = ShastraResolver::new(&self.repo);
+        let files: Vec<String> = self.documents.keys().cloned().collect();
+        for file in files {
+            self.reparse(&file);
+        }
+        Ok(())
+    }
+
+    // _| vyasa lsp reparses only the edited document |_
+    /// Parse one file again - the open document, or else what is on disk - and
+    /// re-index the shastra. False if vyasa doesn't scan the file.
+    fn reparse(&mut self, file: &str) -> bool {
+        let path = Path::new(file);
+        if !self.repo.scans(path) {
+            return false;
+        }
+        let syntax = self.repo.config.comment_syntax(path);
+        let parsed = match self.documents.get(file) {
+            Some(text) => parse_file(text, file, &syntax),
+            None => fs::read_to_string(path).map(|text| parse_file(&text, file, &syntax)).unwrap_or_default(),
+        };
+        self.repo.replace_file(file, parsed);
+        true
+    }
+
+    /// Our file name for a document - None outside this shastra
+    fn file_name(&self, uri: &Url) -> Option<String> {
+        let path = uri.to_file_path().ok()?;
+        path.starts_with(&self.root).then(|| path.to_string_lossy().to_string())
+    }
+
+    /// Current text of a file - the open document, or what is on disk
+    fn text(&self, file: &str) -> String {
+        match self.documents.get(file) {
+            Some(text) => text.clone(),
+            None => fs::read_to_string(file).unwrap_or_default(),
+        }
+    }
+
+    fn location(&self, file: &str, span: Span) -> Option<Location> {
+        // observed shastras are often `../name`
+        let uri = Url::from_file_path(Path::new(file).canonicalize().ok()?).ok()?;
+        Some(Location::new(uri, range(&self.text(file), span)))
+    }
+
+    // _| vyasa lsp publishes check diagnostics |_
+    /// Check the shastra and publish its diagnostics per file, clearing files
+    /// whose violations are gone
+    fn publish_diagnostics(&mut self, connection: &Connection) -> Result<(), String> {
+        let mut by_file: BTreeMap<String, Vec<Diagnostic>> = BTreeMap::new();
+        for diagnostic in check_with(&self.repo, &mut self.resolver) {
+            let Some(file) = diagnostic.file.as_ref().filter(|f| !f.is_empty()) else {
+                continue;
+            };
+            let file = match Path::new(file) {
+                relative if relative.is_relative() => self.root.join(relative).to_string_lossy().to_string(),
+                _ => file.clone(),
+            };
+            let text = self.text(&file);
+            let lsp_diagnostic = to_lsp_diagnostic(&diagnostic, &text);
+            by_file.entry(file).or_default().push(lsp_diagnostic);
+        }
+
+        let cleared: Vec<String> = self.published.iter().filter(|f| !by_file.contains_key(*f)).cloned().collect();
+        for file in cleared {
+            by_file.insert(file, Vec::new());
+        }
+        self.published = by_file.iter().filter(|(_, d)| !d.is_empty()).map(|(f, _)| f.clone()).collect();
+
+        for (file, diagnostics) in by_file {
+            let Ok(uri) = Url::from_file_path(&file) else {
+                continue;
+            };
+            let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+            let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+            connection.sender.send(Message::Notification(notification)).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    // _| vyasa lsp completes anusrits |_
+    /// Mantras to complete inside an anusrit being typed - ours, then those
+    /// of observed shastras, with `@shastra` where the text alone is ambiguous
+    fn completion(&mut self, params: CompletionParams) -> Result<Option<CompletionResponse>, String> {
+        let position = params.text_document_position;
+        let Some(file) = self.file_name(&position.text_document.uri) else {
+            return Ok(None);
+        };
+        let text = self.text(&file);
+        let cursor = byte_at(&text, position.position);
+        let line_start = text[..cursor].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = text[cursor..].find('\n').map_or(text.len(), |i| cursor + i);
+
+        // the cursor must be inside an anusrit that is not closed before it
+        let Some(open) = text[line_start..cursor].rfind(ANUSRIT_OPEN).map(|i| line_start + i) else {
+            return Ok(None);
+        };
+        let typed = &text[open + 2..cursor];
+        if typed.contains("|_") {
+            return Ok(None);
+        }
+        // typing inside an existing anusrit - its closing delimiter stays
+        let rest = &text[cursor..line_end];
+        let closed = rest.find("|_").is_some_and(|i| !rest[..i].contains(ANUSRIT_OPEN));
+
+        let mut candidates: Vec<(String, Option<String>, String)> = Vec::new();
+        for (mantra, info) in &self.repo.mantras {
+            if self.repo.is_live(mantra) {
+                let location = format!("{}:{}", relative_to(&self.repo, &info.file), info.line);
+                candidates.push((mantra.clone(), None, location));
+            }
+        }
+        for alias in self.resolver.aliases() {
+            if let Ok(external) = self.resolver.resolve(&alias) {
+                for mantra in external.mantras.keys().filter(|m| external.is_live(m)) {
+                    candidates.push((mantra.clone(), Some(alias.clone()), format!("@{}", alias)));
+                }
+            }
+        }
+        let mut count: HashMap<&str, usize> = HashMap::new();
+        for (mantra, _, _) in &candidates {
+            *count.entry(mantra.as_str()).or_default() += 1;
+        }
+
+        let replace = range_of(&text, open + 2..cursor);
+        let items = candidates
+            .iter()
+            .map(|(mantra, alias, detail)| {
+                // a local mantra wins over observed ones, so only theirs need the `@`
+                let suffix = match alias {
+                    Some(alias) if count[mantra.as_str()] > 1 => format!("@{}", alias),
+                    _ => String::new(),
+                };
+                let new_text = match closed {
+                    true => format!(" {}", mantra),
+                    false => {
+                        let marker = anusrit_marker(mantra);
+                        format!("{}{}", &marker[2..], suffix)
+                    }
+                };
+                CompletionItem {
+                    label: mantra.clone(),
+                    kind: Some(CompletionItemKind::REFERENCE),
+                    detail: Some(detail.clone()),
+                    sort_text: Some(format!("{}{}", u8::from(alias.is_some()), mantra)),
+                    filter_text: Some(mantra.clone()),
+                    text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(replace, new_text))),
+                    ..CompletionItem::default()
+                }
+            })
+            .collect();
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    // _| vyasa lsp shows the mula bhasya on hover |_
+    fn hover(&mut self, params: HoverParams) -> Result<Option<Hover>, String> {
+        let position = params.text_document_position_params;
+        let Some((target, span)) = self.target_at(&position.text_document.uri, position.position) else {
+            return Ok(None);
+        };
+        let definitions = self.definitions(&target);
+        if definitions.is_empty() {
+            return Ok(None);
+        }
+        let mut sections = Vec::new();
+        for definition in &definitions {
+            let mut lines: Vec<String> = definition
+                .paragraph
+                .lines()
+                .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) })
+                .collect();
+            if let BhasyaKind::Tyakta(note) = &definition.kind {
+                let replacement = note.replacement.as_ref().map(|r| format!(" - use ^{}^ instead", r.mantra_text));
+                lines.insert(0, format!("**tyakta**{}\n", replacement.unwrap_or_default()));
+            }
+            let source = match &definition.shastra {
+                Some(alias) => format!("{}:{} in '{}'", definition.source, definition.line, alias),
+                None => format!("{}:{}", definition.source, definition.line),
+            };
+            lines.push(String::new());
+            lines.push(format!("`{}`", source));
+            sections.push(lines.join("\n"));
+        }
+        let text = self.text(&self.file_name(&position.text_document.uri).unwrap_or_default());
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: sections.join("\n\n---\n\n"),
+            }),
+            range: Some(range(&text, span)),
+        }))
+    }
+
+    // _| vyasa lsp goes from anusrit to mula |_
+    fn definition(&mut self, params: GotoDefinitionParams) -> Result<Option<GotoDefinitionResponse>, String> {
+        let position = params.text_document_position_params;
+        let Some((target, _)) = self.target_at(&position.text_document.uri, position.position) else {
+            return Ok(None);
+        };
+        let locations: Vec<Location> =
+            self.definitions(&target).iter().filter_map(|d| self.location(&d.file, d.mula)).collect();
+        Ok((!locations.is_empty()).then_some(GotoDefinitionResponse::Array(locations)))
+    }
+
+    /// Every anusrit to the mantra under the cursor, and with the declaration
+    /// asked for, its mula definitions here
+    fn references(&mut self, params: ReferenceParams) -> Result<Option<Vec<Location>>, String> {
+        let position = params.text_document_position;
+        let Some((target, _)) = self.target_at(&position.text_document.uri, position.position) else {
+            return Ok(None);
+        };
+        let mut spans: Vec<(String, Span)> =
+            self.uses(&target).into_iter().map(|a| (a.file.clone(), a.span)).collect();
+        if params.context.include_declaration {
+            let definitions = self.definitions(&target);
+            spans.extend(definitions.into_iter().filter(|d| d.shastra.is_none()).map(|d| (d.file, d.mula)));
+        }
+        spans.sort();
+        Ok(Some(spans.iter().filter_map(|(file, span)| self.location(file, *span)).collect()))
+    }
+
+    /// Reword the mantra under the cursor everywhere, as `vyasa rename` does
+    fn rename(&mut self, params: RenameParams) -> Result<Option<WorkspaceEdit>, String> {
+        let position = params.text_document_position;
+        let Some((target, _)) = self.target_at(&position.text_document.uri, position.position) else {
+            return Ok(None);
+        };
+        let plan = rename::plan(&self.repo, &target.mantra, &params.new_name, false)?;
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        let mut texts: HashMap<&str, String> = HashMap::new();
+        for edit in plan.changes.edits() {
+            let Ok(uri) = Url::from_file_path(&edit.file) else {
+                continue;
+            };
+            let text = texts.entry(edit.file.as_str()).or_insert_with(|| self.text(&edit.file));
+            changes.entry(uri).or_default().push(TextEdit::new(range_of(text, edit.range.clone()), edit.text.clone()));
+        }
+        Ok(Some(WorkspaceEdit::new(changes)))
+    }
+
+    /// The mula marker or anusrit at a position, and its span
+    fn target_at(&self, uri: &Url, position: Position) -> Option<(Target, Span)> {
+        let file = self.file_name(uri)?;
+        let byte = byte_at(&self.text(&file), position);
+        let contains = |span: &Span| span.start.byte <= byte && byte <= span.end.byte;
+
+        for bhasya in self.repo.bhasyas.iter().filter(|b| b.file == file) {
+            if let Some(mula) = bhasya.mulas.iter().find(|m| contains(&m.span)) {
+                // a quoted or refuted bhasya's mantras are those of its source
+                let shastra = match &bhasya.kind {
+                    BhasyaKind::Uddhrit(s) | BhasyaKind::Khandita(s) => Some(s.clone()),
+                    _ => self.repo.config.name.clone(),
+                };
+                return Some((Target { mantra: mula.mantra_text.clone(), shastra }, mula.span));
+            }
+        }
+        let anusrit = self.all_anusrits().find(|a| a.file == file && contains(&a.span))?;
+        Some((Target { mantra: anusrit.mantra_text.clone(), shastra: anusrit.shastra.clone() }, anusrit.span))
+    }
+
+    /// Anusrits in prose, in bhasyas and on `tyakta:` lines
+    fn all_anusrits(&self) -> impl Iterator<Item = &Anusrit> {
+        self.repo
+            .anusrits
+            .iter()
+            .chain(self.repo.bhasyas.iter().flat_map(|b| &b.anusrits))
+            .chain(self.repo.tyakta_replacements())
+    }
+
+    /// Where the target is defined: here, in the shastra it names, or - for
+    /// an anusrit we don't define - in every observed shastra that does
+    fn definitions(&mut self, target: &Target) -> Vec<Definition> {
+        let self_name = self.repo.config.name.as_deref();
+        match target.shastra.as_deref() {
+            Some(alias) if Some(alias) != self_name => match self.resolver.resolve(alias) {
+                Ok(external) => defining(external, Some(alias), &target.mantra),
+                Err(_) => Vec::new(),
+            },
+            Some(_) => defining(&self.repo, None, &target.mantra),
+            None if self.repo.is_live(&target.mantra) => defining(&self.repo, None, &target.mantra),
+            None => {
+                let mut definitions = Vec::new();
+                for alias in self.resolver.aliases() {
+                    if let Ok(external) = self.resolver.resolve(&alias) {
+                        if external.is_live(&target.mantra) {
+                            definitions.extend(defining(external, Some(&alias), &target.mantra));
+                        }
+                    }
+                }
+                if definitions.is_empty() {
+                    // abandoned here, perhaps - still worth showing
+                    definitions = defining(&self.repo, None, &target.mantra);
+                }
+                definitions
+            }
+        }
+    }
+
+    /// Anusrits in this shastra resolving to the same mantra as the target
+    fn uses(&self, target: &Target) -> Vec<&Anusrit> {
+        let self_name = self.repo.config.name.as_deref();
+        let ours = match target.shastra.as_deref() {
+            Some(alias) => Some(alias) == self_name,
+            None => self.repo.has_any_bhasya_for_mantra(&target.mantra),
+        };
+        if ours {
+            return self.repo.anusrits_to(&target.mantra);
+        }
+        self.all_anusrits()
+            .filter(|a| a.mantra_text == target.mantra)
+            .filter(|a| a.shastra == target.shastra || a.shastra.is_none())
+            .collect()
+    }
+}
+
+/// The mula bhasyas of `mantra` in `repo`, or its tyakta ones if it has no other
+fn defining(repo: &Repository, alias: Option<&str>, mantra: &str) -> Vec<Definition> {
+    let bhasyas = repo.bhasyas_for_mantra(mantra);
+    let with_kind = |tyakta: bool| -> Vec<Definition> {
+        bhasyas
+            .iter()
+            .filter(|b| matches!(b.kind, BhasyaKind::Tyakta(_)) == tyakta && (tyakta || b.kind == BhasyaKind::Mula))
+            .filter_map(|b| {
+                let mula = b.mulas.iter().find(|m| m.mantra_text == mantra)?;
+                Some(Definition {
+                    shastra: alias.map(str::to_string),
+                    file: b.file.clone(),
+                    source: relative_to(repo, &b.file),
+                    line: mula.span.start.line,
+                    mula: mula.span,
+                    paragraph: b.paragraph.clone(),
+                    kind: b.kind.clone(),
+                })
+            })
+            .collect()
+    };
+    let live = with_kind(false);
+    if live.is_empty() {
+        with_kind(true)
+    } else {
+        live
+    }
+}
+
+fn to_lsp_diagnostic(diagnostic: &CheckDiagnostic, text: &str) -> Diagnostic {
+    let code = serde_json::to_value(diagnostic.rule).ok().and_then(|v| v.as_str().map(str::to_string));
+    let mut message = diagnostic.message.clone();
+    for note in &diagnostic.notes {
+        message.push('\n');
+        message.push_str(note);
+    }
+    Diagnostic {
+        range: diagnostic.span.map(|span| range(text, span)).unwrap_or_default(),
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: code.map(NumberOrString::String),
+        source: Some("vyasa".to_string()),
+        message: format!("{}: {}", diagnostic.rule.heading(), message),
+        ..Diagnostic::default()
+    }
+}
+
+/// Run a request handler on its params, turning errors into error responses
+fn respond<P: DeserializeOwned, R: Serialize>(
+    request: Request,
+    handler: impl FnOnce(P) -> Result<R, String>,
+) -> Response {
+    let id: RequestId = request.id.clone();
+    match serde_json::from_value(request.params) {
+        Ok(params) => match handler(params) {
+            Ok(result) => Response::new_ok(id, result),
+            Err(e) => Response::new_err(id, ErrorCode::RequestFailed as i32, e),
+        },
+        Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+    }
+}
+
+fn extract<P: DeserializeOwned>(notification: Notification) -> Result<P, String> {
+    serde_json::from_value(notification.params).map_err(|e| format!("invalid {} params: {}", notification.method, e))
+}
+
+fn range(text: &str, span: Span) -> Range {
+    range_of(text, span.bytes())
+}
+
+fn range_of(text: &str, bytes: std::ops::Range<usize>) -> Range {
+    Range::new(position(text, bytes.start), position(text, bytes.end))
+}
+
+/// LSP position of a byte offset - columns count UTF-16 code units. Spans
+/// may be stale (the text changed since it was parsed), so the offset is
+/// clamped to the text and to the start of the character it falls in.
+fn position(text: &str, byte: usize) -> Position {
+    let byte = text.floor_char_boundary(byte);
+    let line_start = text[..byte].rfind('\n').map_or(0, |i| i + 1);
+    let line = text[..line_start].matches('\n').count();
+    Position::new(line as u32, text[line_start..byte].encode_utf16().count() as u32)
+}
+
+/// Byte offset of an LSP position, clamped to its line and to the text
+fn byte_at(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let line_end = text[line_start..].find('\n').map_or(text.len(), |i| line_start + i);
+    let mut units = 0;
+    for (i, c) in text[line_start..line_end].char_indices() {
+        if units >= position.character as usize {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    line_end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::mula_marker;
+    use crate::testing::TempShastra;
+    use serde_json::json;
+
+    fn uri(shastra: &TempShastra, file: &str) -> Url {
+        Url::from_file_path(shastra.path(file).canonicalize().unwrap()).unwrap()
+    }
+
+    /// Params of a request at `line`:`character` of `file`, with `extra` fields
+    fn params<P: DeserializeOwned>(uri: &Url, line: u32, character: u32, extra: serde_json::Value) -> P {
+        let mut params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character },
+        });
+        params.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(params).unwrap()
+    }
+
+    #[test]
+    fn positions_count_utf16_units_on_non_ascii_lines() {
+        let text = "धर्म 😀 x\nb";
+        let x = text.find('x').unwrap();
+        assert_eq!(position(text, x), Position::new(0, 8));
+        assert_eq!(byte_at(text, Position::new(0, 8)), x);
+        assert_eq!(byte_at(text, Position::new(1, 0)), x + 2);
+        // past the end of a line, or of the text
+        assert_eq!(byte_at(text, Position::new(0, 40)), x + 1);
+        assert_eq!(byte_at(text, Position::new(5, 0)), text.len());
+    }
+
+    #[test]
+    fn stale_spans_do_not_split_characters() {
+        let text = "धर्म 😀 x\nb";
+        let emoji = text.find('😀').unwrap();
+        assert_eq!(position(text, emoji + 2), Position::new(0, 5));
+        assert_eq!(position(text, 1), Position::new(0, 0));
+        assert_eq!(position(text, 100), Position::new(1, 1));
+    }
+
+    #[test]
+    fn completion_offers_mantras_inside_an_open_anusrit() {
+        let notes = format!("> {} - duty.\n", mula_marker("धर्म is duty"));
+        let shastra = TempShastra::new("lsp-complete", &[("notes.md", &notes), ("use.md", "")]);
+        shastra.write("use.md", &format!("see {}धर्", ANUSRIT_OPEN));
+        let mut server = Server::new(&shastra.root).unwrap();
+
+        let uri = uri(&shastra, "use.md");
+        let response = server.completion(params(&uri, 0, 9, json!({}))).unwrap();
+        let Some(CompletionResponse::Array(items)) = response else {
+            panic!("expected completion items");
+        };
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].label, "धर्म is duty");
+        let Some(CompletionTextEdit::Edit(edit)) = &items[0].text_edit else {
+            panic!("expected a text edit");
+        };
+        assert_eq!(edit.range, Range::new(Position::new(0, 6), Position::new(0, 9)));
+        assert_eq!(edit.new_text, " धर्म is duty |_");
+
+        // outside an anusrit there is nothing to complete
+        let response = server.completion(params(&uri, 0, 2, json!({}))).unwrap();
+        assert!(response.is_none());
+    }
+
+    #[test]
+    fn definition_goes_from_anusrit_to_mula() {
+        let notes = format!("# notes\n\n> {} - duty.\n", mula_marker("धर्म"));
+        let usage = format!("the {} of it\n", anusrit_marker("धर्म"));
+        let shastra = TempShastra::new("lsp-definition", &[("notes.md", &notes), ("use.md", &usage)]);
+        let mut server = Server::new(&shastra.root).unwrap();
+
+        let response = server.definition(params(&uri(&shastra, "use.md"), 0, 8, json!({}))).unwrap();
+        let Some(GotoDefinitionResponse::Array(locations)) = response else {
+            panic!("expected a definition");
+        };
+        assert_eq!(locations.len(), 1);
+        assert_eq!(locations[0].uri, uri(&shastra, "notes.md"));
+        assert_eq!(locations[0].range, Range::new(Position::new(2, 2), Position::new(2, 12)));
+
+        // plain prose is not a target
+        let response = server.definition(params(&uri(&shastra, "use.md"), 0, 1, json!({}))).unwrap();
+        assert!(response.is_none());
+    }
+
+    #[test]
+    fn rename_edits_the_mula_and_every_anusrit() {
+        let notes = format!("> {} - duty.\n", mula_marker("धर्म"));
+        let usage = format!("the {} of it\n", anusrit_marker("धर्म"));
+        let shastra = TempShastra::new("lsp-rename", &[("notes.md", &notes), ("use.md", &usage)]);
+        let mut server = Server::new(&shastra.root).unwrap();
+
+        let rename = params(&uri(&shastra, "use.md"), 0, 8, json!({ "newName": "righteous duty" }));
+        let edit = server.rename(rename).unwrap().unwrap();
+        let changes = edit.changes.unwrap();
+
+        let in_use = &changes[&uri(&shastra, "use.md")];
+        assert_eq!(in_use.len(), 1);
+        assert_eq!(in_use[0].new_text, anusrit_marker("righteous duty"));
+        assert_eq!(in_use[0].range, Range::new(Position::new(0, 4), Position::new(0, 14)));
+        let in_notes = &changes[&uri(&shastra, "notes.md")];
+        assert!(in_notes.iter().any(|e| e.new_text.contains(&mula_marker("righteous duty"))));
+    }
+
+    #[test]
+    fn saving_an_observed_shastra_parses_it_again() {
+        let other = TempShastra::new("lsp-observed", &[("theirs.md", "nothing yet\n")]);
+        let usage = format!("the {}\n", anusrit_marker("karma"));
+        let shastra = TempShastra::new("lsp-observer", &[("use.md", &usage)]);
+        shastra.observe("other", &other);
+        let mut server = Server::new(&shastra.root).unwrap();
+        let at = || params(&uri(&shastra, "use.md"), 0, 7, json!({}));
+        assert!(server.definition(at()).unwrap().is_none());
+
+        other.write("theirs.md", &format!("> {} - action.\n", mula_marker("karma")));
+        let saved = json!({ "textDocument": { "uri": uri(&other, "theirs.md") } });
+        let saved = Notification::new(DidSaveTextDocument::METHOD.to_string(), saved);
+        assert!(server.handle_notification(saved).unwrap());
+        assert!(server.definition(at()).unwrap().is_some());
+    }
+}
@@ -1,20 +1,41 @@
-use clap::Parser;
-use std::path::PathBuf;
+use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
 
-mod check;
-mod mantra;
-mod parser;
-mod stats;
+mod cli {
+    pub mod build;
+    pub mod canon;
+    pub mod check;
+    pub mod fmt;
+    pub mod init;
+    pub mod khandita;
+    pub mod lsp;
+    pub mod mantra;
+    pub mod observe;
+    pub mod quote;
+    pub mod rename;
+    pub mod serve;
+    pub mod stats;
+    #[cfg(test)]
+    pub mod testing;
+    pub mod tyakta;
+    pub mod watch;
+}
+
+use cli::{build, canon, check, fmt, init, khandita, lsp, mantra, observe, quote, rename, serve, stats, tyakta, watch};
 
 #[derive(Parser)]
 #[command(name = "vyasa")]
 #[command(about = "A tool to organize and curate knowledge through mantras")]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Mantra text to search for (omit to run check + stats)
     mantra: Option<String>,
 
     /// Path to the repository (defaults to current directory)
-    #[arg(long, short, default_value = ".")]
+    #[arg(long, short, default_value = ".", global = true)]
     path: PathBuf,
 
     /// Show anusrits when searching for a mantra
@@ -22,12 +43,226 @@ struct Cli {
     anusrits: bool,
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Check every rule, exiting non-zero on violations
+    Check {
+        /// Folder to check (overrides --path)
+        folder: Option<PathBuf>,
+
+        /// Apply the safe fixes for the violations that have one
+        #[arg(long)]
+        fix: bool,
+
+        /// Also apply unsafe fixes - ones that change more than the flagged text
+        #[arg(long = "unsafe")]
+        unsafe_fixes: bool,
+
+        /// Show the fixes as a diff instead of applying them
+        #[arg(long)]
+        diff: bool,
+
+        /// Observed shastra to resolve ambiguous anusrits to, with an `@` suffix
+        #[arg(long, value_name = "NAME")]
+        shastra: Option<String>,
+    },
+    /// Show repository statistics
+    Stats {
+        /// Folder to analyze (overrides --path)
+        folder: Option<PathBuf>,
+    },
+    /// Show the bhasyas (and optionally anusrits) of a mantra
+    Mantra {
+        /// Mantra text to search for
+        text: String,
+
+        /// Show anusrits of the mantra
+        #[arg(long, short)]
+        anusrits: bool,
+    },
+    /// Write canon.md, a digest of every live bhasya grouped by file
+    Canon {
+        /// Also include bhasyas quoted from other shastras (uddhrit)
+        #[arg(long)]
+        uddhrit: bool,
+
+        /// Fail if canon.md is not up to date instead of writing it
+        #[arg(long)]
+        check: bool,
+
+        /// Write to this file instead of canon.md at the repository root
+        #[arg(long, short)]
+        out: Option<PathBuf>,
+    },
+    /// Write the shastra as a static html site: a page per file and per mantra, and a searchable index
+    Build {
+        /// Folder to write the site to
+        #[arg(long, short, default_value = "site")]
+        out: PathBuf,
+    },
+    /// Lay out every bhasya consistently: mantra first, one separator, wrapped to a width
+    Fmt {
+        /// Fail if any bhasya is not formatted, showing the diff, instead of writing
+        #[arg(long)]
+        check: bool,
+    },
+    /// Make a folder a shastra: .vyasa/config.json, shastra.json and .gitignore entries
+    Init {
+        /// Folder to initialize (overrides --path)
+        folder: Option<PathBuf>,
+
+        /// Name of the shastra (defaults to the folder name)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Also write mantras.md with a sample bhasya
+        #[arg(long)]
+        seed: bool,
+    },
+    /// List the khanditas we have issued
+    Khandita {
+        /// Check each source shastra: pending until it marks the bhasya tyakta
+        #[arg(long)]
+        status: bool,
+    },
+    /// Run the language server on stdin/stdout, for editors
+    Lsp,
+    /// Observe another shastra under an alias, or list observed shastras
+    Observe {
+        /// Alias to refer to the shastra by (omit to list)
+        #[arg(requires = "target")]
+        alias: Option<String>,
+
+        /// Folder of the shastra, relative to the repository root
+        target: Option<String>,
+
+        /// Write to shastra.local.json (a per-machine override) instead of shastra.json
+        #[arg(long)]
+        local: bool,
+    },
+    /// Quote a bhasya from an observed shastra as uddhrit (prints it unless --into)
+    Quote {
+        /// Alias of the observed shastra
+        shastra: String,
+
+        /// Mantra whose mula bhasya to quote
+        mantra: String,
+
+        /// Append the quote to this file, in its comment style (dry run unless --write)
+        #[arg(long, value_name = "FILE")]
+        into: Option<PathBuf>,
+
+        /// Apply the changes instead of only showing the diff
+        #[arg(long, requires = "into")]
+        write: bool,
+    },
+    /// Refute a bhasya of an observed shastra with a khandita block (prints it unless --into)
+    Refute {
+        /// Alias of the observed shastra
+        shastra: String,
+
+        /// Mantra whose bhasya to refute
+        mantra: String,
+
+        /// Append the block to this file, in its comment style (dry run unless --write)
+        #[arg(long, value_name = "FILE")]
+        into: Option<PathBuf>,
+
+        /// Apply the changes instead of only showing the diff
+        #[arg(long, requires = "into")]
+        write: bool,
+    },
+    /// Reword a mantra: its mula definitions and every anusrit (dry run unless --write)
+    Rename {
+        /// Current mantra text
+        old: String,
+
+        /// New mantra text
+        new: String,
+
+        /// Apply the changes instead of only showing the diff
+        #[arg(long)]
+        write: bool,
+
+        /// Leave a tyakta bhasya for the old text pointing to the new one
+        #[arg(long)]
+        tyakta: bool,
+    },
+    /// Serve the shastra as linked html pages, reloading them as files change
+    Serve {
+        /// Port to listen on, on localhost
+        #[arg(long, default_value_t = 8000)]
+        port: u16,
+    },
+    /// Deprecate a mantra: mark its bhasyas tyakta and list what will break (dry run unless --write)
+    Tyakta {
+        /// Mantra text to deprecate
+        mantra: String,
+
+        /// Live mantra to use instead - check suggests it wherever the old one is used
+        #[arg(long, value_name = "MANTRA")]
+        replacement: Option<String>,
+
+        /// Apply the changes instead of only showing the diff
+        #[arg(long)]
+        write: bool,
+    },
+    /// Keep checking as files change, printing new and resolved violations
+    Watch {
+        /// Folder to watch (overrides --path)
+        folder: Option<PathBuf>,
+    },
+    /// Stop observing a shastra
+    Unobserve {
+        /// Alias to remove
+        alias: String,
+
+        /// Remove from shastra.local.json instead of shastra.json
+        #[arg(long)]
+        local: bool,
+    },
+}
+
 fn main() {
     let cli = Cli::parse();
 
-    let result = match cli.mantra {
-        Some(text) => mantra::run(&cli.path, &text, cli.anusrits),
-        None => run_check_and_stats(&cli.path),
+    let result = match cli.command {
+        Some(Command::Check { folder, fix, unsafe_fixes, diff, shastra }) => {
+            let path = folder.as_deref().unwrap_or(&cli.path);
+            if fix || unsafe_fixes || diff || shastra.is_some() {
+                check::fix(path, unsafe_fixes, diff, shastra.as_deref())
+            } else {
+                check::run(path)
+            }
+        }
+        Some(Command::Stats { folder }) => stats::run(folder.as_deref().unwrap_or(&cli.path)),
+        Some(Command::Mantra { text, anusrits }) => mantra::run(&cli.path, &text, anusrits),
+        Some(Command::Canon { uddhrit, check, out }) => canon::run(&cli.path, uddhrit, check, out),
+        Some(Command::Build { out }) => build::run(&cli.path, &out),
+        Some(Command::Fmt { check }) => fmt::run(&cli.path, check),
+        Some(Command::Init { folder, name, seed }) => {
+            init::run(folder.as_deref().unwrap_or(&cli.path), name, seed)
+        }
+        Some(Command::Khandita { status }) => khandita::list(&cli.path, status),
+        Some(Command::Lsp) => lsp::run(&cli.path),
+        Some(Command::Observe { alias, target, local }) => observe::observe(&cli.path, alias, target, local),
+        Some(Command::Quote { shastra, mantra, into, write }) => {
+            quote::run(&cli.path, &shastra, &mantra, into.as_deref(), write)
+        }
+        Some(Command::Refute { shastra, mantra, into, write }) => {
+            khandita::refute(&cli.path, &shastra, &mantra, into.as_deref(), write)
+        }
+        Some(Command::Rename { old, new, write, tyakta }) => rename::run(&cli.path, &old, &new, write, tyakta),
+        Some(Command::Serve { port }) => serve::run(&cli.path, port),
+        Some(Command::Tyakta { mantra, replacement, write }) => {
+            tyakta::run(&cli.path, &mantra, replacement.as_deref(), write)
+        }
+        Some(Command::Unobserve { alias, local }) => observe::unobserve(&cli.path, &alias, local),
+        Some(Command::Watch { folder }) => watch::run(folder.as_deref().unwrap_or(&cli.path)),
+        None => match cli.mantra {
+            Some(text) => mantra::run(&cli.path, &text, cli.anusrits),
+            None => run_check_and_stats(&cli.path),
+        },
     };
 
     if let Err(e) = result {
@@ -36,7 +271,7 @@ fn main() {
     }
 }
 
-fn run_check_and_stats(path: &PathBuf) -> Result<(), String> {
+fn run_check_and_stats(path: &Path) -> Result<(), String> {
     // run check first
     let check_result = check::run(path);
 
@@ -1,7 +1,44 @@
+use crate::cache::ParseCache;
+use crate::comment::{BlockComment, CommentSyntax};
+use crate::config::{load_config, load_shastra_config};
+use chrono::NaiveDate;
+pub use crate::config::{Config, ConfigError, ShastraConfig};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
-use walkdir::WalkDir;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::WalkBuilder;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A position in a source file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Position {
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column, counted in characters
+    pub column: usize,
+    /// 0-based byte offset from the start of the file
+    pub byte: usize,
+}
+
+/// A range in a source file - `end` is exclusive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    /// Byte range of the span in its file
+    pub fn bytes(&self) -> Range<usize> {
+        self.start.byte..self.end.byte
+    }
+}
 
 /// Information about a mantra in this repository
 #[derive(Debug, Clone, Default)]
@@ -9,6 +46,8 @@ pub struct MantraInfo {
     /// First definition location (for display)
     pub file: String,
     pub line: usize,
+    /// Span of the first `**^mantra^**` definition
+    pub span: Span,
     /// Whether this mantra has commentary
     pub has_explanation: bool,
     /// Bhasya indices where this is a mula definition (**^mantra^**)
@@ -18,7 +57,7 @@ pub struct MantraInfo {
 }
 
 /// The kind of bhasya - determines its semantic meaning
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BhasyaKind {
     /// Regular bhasya - defines a mula mantra
     Mula,
@@ -26,50 +65,107 @@ pub enum BhasyaKind {
     Uddhrit(String),
     /// Refuting a shastra's bhasya (khandita खण्डित)
     Khandita(String),
-    /// Deprecated bhasya (tyakta त्यक्त)
-    Tyakta,
+    /// Deprecated bhasya (tyakta त्यक्त), with what its `tyakta:` line records
+    Tyakta(TyaktaNote),
+}
+
+/// What a `tyakta:` line says about the bhasya it abandons - all optional:
+/// `tyakta: 2024-05-01 -> _| replacement mantra |_ reason`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TyaktaNote {
+    /// When the bhasya was abandoned
+    pub date: Option<NaiveDate>,
+    /// The mantra to use instead, written as an anusrit after `->`
+    pub replacement: Option<Anusrit>,
+    /// Any text after the date and replacement
+    pub reason: Option<String>,
 }
 
 /// A bhasya is a quote block (the teaching unit containing mantras and commentary)
 /// - `> **^mantra^**` - contains a mula mantra definition (Mula)
 /// - `shastra: name\n> ...` - quotes canonical location in a shastra (Uddhrit)
 /// - `khandita: name\n> ...` - refutes a shastra's bhasya (Khandita)
-/// - `tyakta:\n> ...` - deprecates this bhasya (Tyakta)
-#[derive(Debug, Clone)]
+/// - `tyakta:\n> ...` - deprecates this bhasya (Tyakta), optionally naming a
+///   date and replacement: `tyakta: 2024-05-01 -> _| new mantra |_`
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Bhasya {
     /// Original paragraph text (preserves structure with newlines)
     pub paragraph: String,
     pub file: String,
     pub line: usize,
+    /// Span of the whole quote block (prefix lines like `tyakta:` excluded)
+    pub span: Span,
     /// The kind of bhasya (mula, uddhrit, khandita, or tyakta)
     pub kind: BhasyaKind,
+    /// `**^mantra^**` definitions in this bhasya
+    pub mulas: Vec<Mula>,
+    /// `_| mantra |_` anusrits inside this bhasya
+    pub anusrits: Vec<Anusrit>,
+}
+
+/// A mula mantra definition (`**^mantra^**`) inside a bhasya
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mula {
+    pub mantra_text: String,
+    /// Span of the whole `**^mantra^**` marker
+    pub span: Span,
 }
 
 /// An anusrit (अनुसृत) is a mantra reference outside a bhasya using `_| mantra text |_` syntax
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Anusrit {
     pub mantra_text: String,
     pub file: String,
     pub line: usize,
+    /// Span of the whole `_| mantra |_` marker (the `@shastra` suffix excluded)
+    pub span: Span,
     /// `_| mantra |_@shastra-name` for external anusrits
     pub shastra: Option<String>,
 }
 
-/// Repository configuration loaded from .vyasa/config.json
-#[derive(Debug, Default)]
-pub struct Config {
-    /// Name of this shastra (used for self-references)
-    pub name: Option<String>,
+/// A mantra spelled differently from its canonical form (see `normalize_mantra`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NonCanonicalSpelling {
+    /// Text as written between the delimiters (trimmed)
+    pub raw: String,
+    pub canonical: String,
+    pub file: String,
+    pub line: usize,
+    pub span: Span,
 }
 
-/// Shastra configuration loaded from .vyasa/shastra.json
-/// Maps alias names to paths (local folders)
-#[derive(Debug, Default)]
-pub struct ShastraConfig {
-    /// alias -> path mapping (merged from shastra.json and shastra.local.json)
-    pub aliases: HashMap<String, String>,
+/// Everything parsed from one file, before it is merged into a repository
+/// (bhasya indices are local to the file)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ParsedFile {
+    pub bhasyas: Vec<Bhasya>,
+    /// Anusrits outside bhasyas
+    pub anusrits: Vec<Anusrit>,
+    pub non_canonical: Vec<NonCanonicalSpelling>,
 }
 
+impl ParsedFile {
+    /// Point every parsed element at the given file
+    pub fn set_file_name(&mut self, file_name: &str) {
+        for bhasya in &mut self.bhasyas {
+            bhasya.file = file_name.to_string();
+            for anusrit in &mut bhasya.anusrits {
+                anusrit.file = file_name.to_string();
+            }
+            if let BhasyaKind::Tyakta(TyaktaNote { replacement: Some(anusrit), .. }) = &mut bhasya.kind {
+                anusrit.file = file_name.to_string();
+            }
+        }
+        for anusrit in &mut self.anusrits {
+            anusrit.file = file_name.to_string();
+        }
+        for spelling in &mut self.non_canonical {
+            spelling.file = file_name.to_string();
+        }
+    }
+}
+
+/// A parsed shastra - every bhasya and anusrit under a path, with its mantras indexed
 #[derive(Debug, Default)]
 pub struct Repository {
     /// All mantras indexed by text
@@ -78,37 +174,58 @@ pub struct Repository {
     pub bhasyas: Vec<Bhasya>,
     /// Anusrits outside bhasyas (for validation)
     pub anusrits: Vec<Anusrit>,
+    /// Mula mantras and anusrits whose spelling is not canonical
+    pub non_canonical: Vec<NonCanonicalSpelling>,
     pub config: Config,
     pub shastra_config: ShastraConfig,
+    /// Problems in the `.vyasa` config files (defaults are used in their place)
+    pub config_errors: Vec<ConfigError>,
+    /// Folder holding `.vyasa` (or `.git`), if any
+    pub root: Option<PathBuf>,
 }
 
 impl Repository {
+    /// Parse every file under `path`, loading config from the repository root above it
     // _| vyasa check checks all non human meant files |_
     pub fn parse(path: &Path) -> Result<Self, String> {
+        Repository::parse_with_cache(path, true)
+    }
+
+    /// `parse`, reading and writing `.vyasa/cache/` only with `cache` -
+    /// observed shastras are parsed without it, so vyasa never writes into them
+    pub fn parse_with_cache(path: &Path, cache: bool) -> Result<Self, String> {
         let mut repo = Repository::default();
 
         // find repository root and load config
         let repo_root = find_repo_root(path);
         if let Some(root) = &repo_root {
-            repo.config = load_config(root);
-            repo.shastra_config = load_shastra_config(root);
+            let (config, mut errors) = load_config(root);
+            let (shastra_config, shastra_errors) = load_shastra_config(root);
+            errors.extend(shastra_errors);
+            errors.sort_by(|a, b| (&a.file, a.span).cmp(&(&b.file, b.span)));
+            repo.config = config;
+            repo.shastra_config = shastra_config;
+            repo.config_errors = errors;
         }
-
-        for entry in WalkDir::new(path)
-            .into_iter()
+        repo.root = repo_root.clone();
+
+        let filter = FileFilter::new(&repo.config)?;
+        let filter_root = repo_root.as_deref().unwrap_or(path);
+
+        // hidden files and anything in .gitignore / .ignore are skipped;
+        // sorted so bhasya order is the same on every machine
+        // _| vyasa never scans a built site |_
+        let mut files = Vec::new();
+        for entry in WalkBuilder::new(path)
+            .require_git(false)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .filter_entry(|entry| !entry.file_type().is_some_and(|t| t.is_dir()) || !is_site(entry.path()))
+            .build()
             .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
+            .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
         {
             let file_path = entry.path();
 
-            // skip hidden files and directories (but not . or ..)
-            if file_path.components().any(|c| {
-                let s = c.as_os_str().to_string_lossy();
-                s.starts_with('.') && s.len() > 2
-            }) {
-                continue;
-            }
-
             // _| vyasa check checks all non human meant files |_
             // skip binary and human-meant files (xml, images, etc.)
             let ext = file_path.extension().and_then(|e| e.to_str()).unwrap_or("");
@@ -116,18 +233,47 @@ impl Repository {
                 continue;
             }
 
-            // skip canon.md - it's a digest file, not a source
-            if file_path.file_name().map_or(false, |n| n == "canon.md") {
+            // include / exclude globs from config (canon.md is always excluded)
+            let relative = file_path.strip_prefix(filter_root).unwrap_or(file_path);
+            if !filter.accepts(relative) {
                 continue;
             }
 
-            let content = match fs::read_to_string(file_path) {
-                Ok(c) => c,
-                Err(_) => continue,
-            };
+            files.push(entry.into_path());
+        }
 
-            let file_name = file_path.to_string_lossy().to_string();
-            parse_file(&content, &file_name, &mut repo);
+        // unchanged files come from .vyasa/cache/ instead of being re-parsed
+        let cache = repo_root
+            .as_deref()
+            .filter(|_| cache && repo.config.cache)
+            .and_then(ParseCache::open);
+
+        // read and parse files in parallel; collect keeps walk order, so
+        // merging assigns the same bhasya indices as a sequential parse
+        let config = &repo.config;
+        let parsed: Vec<ParsedFile> = files
+            .par_iter()
+            .filter_map(|file_path| {
+                let file_name = file_path.to_string_lossy().to_string();
+                let syntax = config.comment_syntax(file_path);
+                match &cache {
+                    Some(cache) => cache.get_or_parse(file_path, &file_name, &syntax),
+                    None => {
+                        let content = fs::read_to_string(file_path).ok()?;
+                        Some(parse_file(&content, &file_name, &syntax))
+                    }
+                }
+            })
+            .collect();
+
+        if let Some(cache) = cache {
+            // entries of files outside a partial scan must survive it
+            let whole_shastra = repo_root.as_deref().is_some_and(|root| same_dir(root, path));
+            cache.save(whole_shastra);
+        }
+
+        for file in parsed {
+            repo.add_file(file);
         }
 
         // determine which mantras have explanations (commentary in same paragraph)
@@ -136,25 +282,58 @@ impl Repository {
         Ok(repo)
     }
 
-    /// Get mantras without explanations
+    /// Merge one file's parse into the repository, indexing its mantras
+    pub fn add_file(&mut self, file: ParsedFile) {
+        for bhasya in file.bhasyas {
+            let idx = self.bhasyas.len();
+            let is_mula = matches!(bhasya.kind, BhasyaKind::Mula);
+
+            for mula in &bhasya.mulas {
+                // add to mantras with mula_bhasyas
+                let entry = self.mantras.entry(mula.mantra_text.clone()).or_default();
+                entry.mula_bhasyas.push(idx);
+
+                // set first definition location if not set
+                if entry.file.is_empty() {
+                    entry.file = bhasya.file.clone();
+                    entry.line = mula.span.start.line;
+                    entry.span = mula.span;
+                }
+
+                // mark as explained if this is a Mula bhasya
+                if is_mula {
+                    entry.has_explanation = true;
+                }
+            }
+
+            for anusrit in &bhasya.anusrits {
+                let entry = self.mantras.entry(anusrit.mantra_text.clone()).or_default();
+                entry.anusrit_bhasyas.push(idx);
+            }
+
+            self.bhasyas.push(bhasya);
+        }
+        self.anusrits.extend(file.anusrits);
+        self.non_canonical.extend(file.non_canonical);
+    }
+
+    /// Get mantras without explanations, in source order
     pub fn unexplained_mantras(&self) -> Vec<(&str, &MantraInfo)> {
-        self.mantras
+        let mut unexplained: Vec<_> = self.mantras
             .iter()
             .filter(|(_, m)| !m.has_explanation)
             .map(|(text, info)| (text.as_str(), info))
-            .collect()
+            .collect();
+        unexplained.sort_by(|a, b| (&a.1.file, a.1.span, a.0).cmp(&(&b.1.file, b.1.span, b.0)));
+        unexplained
     }
 
     /// Get all mula mantras with their associated bhasyas
-    /// Returns (mantra_text, &Bhasya) for each mula occurrence
+    /// Returns (mantra_text, &Bhasya) for each mula occurrence, in source order
     pub fn mula_mantras_with_bhasyas(&self) -> Vec<(&str, &Bhasya)> {
-        self.mantras
+        self.bhasyas
             .iter()
-            .flat_map(|(mantra_text, info)| {
-                info.mula_bhasyas.iter().filter_map(move |&idx| {
-                    self.bhasyas.get(idx).map(|b| (mantra_text.as_str(), b))
-                })
-            })
+            .flat_map(|b| b.mulas.iter().map(move |m| (m.mantra_text.as_str(), b)))
             .collect()
     }
 
@@ -171,6 +350,74 @@ impl Repository {
             .unwrap_or_default()
     }
 
+    /// Every anusrit to one of our mantras - in prose, code comments,
+    /// bhasyas and `tyakta: -> ...` lines - unless it names another shastra with `@`
+    pub fn anusrits_to(&self, mantra_text: &str) -> Vec<&Anusrit> {
+        let self_name = self.config.name.as_deref();
+        let mut anusrits: Vec<&Anusrit> = self
+            .anusrits
+            .iter()
+            .chain(self.bhasyas.iter().flat_map(|b| &b.anusrits))
+            .chain(self.tyakta_replacements())
+            .filter(|a| a.mantra_text == mantra_text)
+            .filter(|a| a.shastra.is_none() || a.shastra.as_deref() == self_name)
+            .collect();
+        anusrits.sort_by(|a, b| (&a.file, a.span).cmp(&(&b.file, b.span)));
+        anusrits
+    }
+
+    /// The `-> _| replacement |_` anusrits of every tyakta bhasya
+    pub fn tyakta_replacements(&self) -> impl Iterator<Item = &Anusrit> {
+        self.bhasyas.iter().filter_map(|b| match &b.kind {
+            BhasyaKind::Tyakta(note) => note.replacement.as_ref(),
+            _ => None,
+        })
+    }
+
+    /// The `tyakta:` line of a tyakta mantra's first bhasya
+    pub fn tyakta_note(&self, mantra_text: &str) -> Option<&TyaktaNote> {
+        if !self.is_tyakta(mantra_text) {
+            return None;
+        }
+        match &self.bhasyas[self.mantras[mantra_text].mula_bhasyas[0]].kind {
+            BhasyaKind::Tyakta(note) => Some(note),
+            _ => None,
+        }
+    }
+
+    /// The replacement named by a tyakta mantra's bhasyas, if it is tyakta
+    /// and one of them names one
+    pub fn replacement_for(&self, mantra_text: &str) -> Option<&Anusrit> {
+        if !self.is_tyakta(mantra_text) {
+            return None;
+        }
+        self.mantras[mantra_text].mula_bhasyas.iter().find_map(|&idx| match &self.bhasyas[idx].kind {
+            BhasyaKind::Tyakta(note) => note.replacement.as_ref(),
+            _ => None,
+        })
+    }
+
+    /// Whether a mantra is defined by at least one bhasya that is not tyakta -
+    /// only live mantras can be used via anusrit
+    pub fn is_live(&self, mantra_text: &str) -> bool {
+        self.mantras.get(mantra_text).is_some_and(|info| {
+            info.mula_bhasyas
+                .iter()
+                .any(|&idx| !matches!(self.bhasyas[idx].kind, BhasyaKind::Tyakta(_)))
+        })
+    }
+
+    /// Whether every bhasya defining a mantra is tyakta - abandoned, so it
+    /// needs no explanation but can no longer be used
+    pub fn is_tyakta(&self, mantra_text: &str) -> bool {
+        self.mantras.get(mantra_text).is_some_and(|info| {
+            !info.mula_bhasyas.is_empty()
+                && info.mula_bhasyas
+                    .iter()
+                    .all(|&idx| matches!(self.bhasyas[idx].kind, BhasyaKind::Tyakta(_)))
+        })
+    }
+
     /// Check if a mantra text exists as mula in any bhasya
     pub fn has_any_bhasya_for_mantra(&self, mantra_text: &str) -> bool {
         self.mantras
@@ -178,120 +425,287 @@ impl Repository {
             .map(|info| !info.mula_bhasyas.is_empty())
             .unwrap_or(false)
     }
+
+    /// Replace what was parsed from `file_name` with `file` - the parse of an
+    /// edited document - keeping its place in file order, and re-index the
+    /// mantras. No other file is read or parsed again.
+    pub fn replace_file(&mut self, file_name: &str, mut file: ParsedFile) {
+        file.set_file_name(file_name);
+        let bhasyas = splice_file(std::mem::take(&mut self.bhasyas), file.bhasyas, |b| &b.file, file_name);
+        let anusrits = splice_file(std::mem::take(&mut self.anusrits), file.anusrits, |a| &a.file, file_name);
+        let non_canonical =
+            splice_file(std::mem::take(&mut self.non_canonical), file.non_canonical, |s| &s.file, file_name);
+        self.mantras.clear();
+        self.add_file(ParsedFile { bhasyas, anusrits, non_canonical });
+        mark_explained_mantras(self);
+    }
+
+    /// Whether `parse` would scan `file` - it is not a skipped file type, the
+    /// include / exclude globs accept it and the walk from the root reaches it
+    pub fn scans(&self, file: &Path) -> bool {
+        let ext = file.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let relative = self.root.as_deref().and_then(|root| file.strip_prefix(root).ok()).unwrap_or(file);
+        !should_skip_file(ext)
+            && FileFilter::new(&self.config).is_ok_and(|filter| filter.accepts(relative))
+            && !self.root.as_deref().is_some_and(|root| walk_skips(root, relative))
+    }
 }
 
+/// `items` with those of `file_name` replaced by `new`, where the old ones were
+/// (or at the end)
+fn splice_file<T>(items: Vec<T>, new: Vec<T>, file_of: impl Fn(&T) -> &String, file_name: &str) -> Vec<T> {
+    let at = items.iter().position(|item| file_of(item) == file_name).unwrap_or(items.len());
+    let mut kept: Vec<T> = items.into_iter().filter(|item| file_of(item) != file_name).collect();
+    kept.splice(at..at, new);
+    kept
+}
+
+/// Parse one file's content, using `syntax` to find bhasyas in comments
 // _| mantras should use inline syntax not block because they are meant to be short |_
-fn parse_file(content: &str, file_name: &str, repo: &mut Repository) {
-    let lines: Vec<&str> = content.lines().collect();
+pub fn parse_file(content: &str, file_name: &str, syntax: &CommentSyntax) -> ParsedFile {
+    let mut file = ParsedFile::default();
 
-    // first pass: identify code block regions to skip
-    let mut in_code_block = false;
-    let mut skip_lines: Vec<bool> = vec![false; lines.len()];
-
-    for (i, line) in lines.iter().enumerate() {
-        if line.trim().starts_with("```") {
-            in_code_block = !in_code_block;
-            skip_lines[i] = true;
-        } else {
-            skip_lines[i] = in_code_block;
-        }
-    }
+    // first pass: locate code blocks and comments line by line
+    let lines = scan_lines(content, syntax);
 
     // second pass: identify paragraphs and parse them
-    let paragraphs = extract_paragraphs(&lines, &skip_lines);
+    let paragraphs = extract_paragraphs(&lines);
 
     for para in paragraphs {
-        parse_paragraph(&para, file_name, repo);
+        parse_paragraph(&para, &lines, file_name, &mut file);
     }
+
+    file
 }
 
+/// A source line with its markdown code block and comment markers located
 #[derive(Debug)]
-struct Paragraph {
-    text: String,
-    lines: Vec<(usize, String)>,  // (line_num, text)
-    /// True if this paragraph is deprecated (from preceding `tyakta:` line)
-    is_deprecated: bool,
-    /// If set, this paragraph is attributed to a shastra (from preceding `shastra: name` line)
-    shastra: Option<String>,
-    /// If set, this paragraph refutes a bhasya from another shastra (from preceding `khandita: name` line)
-    khandita: Option<String>,
+struct SourceLine<'a> {
+    text: &'a str,
+    /// Byte offset of the line start in the file
+    offset: usize,
+    /// Byte range of `text` inside comment markers (all of `text` if not a comment)
+    content: Range<usize>,
+    /// The comment marker - the line prefix (e.g. `  //`), or the opening
+    /// delimiter for every line of a block comment (e.g. `/*`)
+    marker: Option<&'a str>,
+    /// Inside (or delimiting) a markdown code block - skipped entirely
+    in_code_block: bool,
+    /// `content` is the inside of a string literal, whose escapes `resolved` reads
+    escaped: bool,
 }
 
-/// Strip common comment prefixes from a line, returning the content after the prefix
-/// Returns (stripped_content, comment_prefix) or None if no comment prefix found
-fn strip_comment_prefix(line: &str) -> Option<(&str, &str)> {
-    let trimmed = line.trim_start();
-
-    // try each comment prefix in order of specificity
-    let prefixes = ["//", "#", "--", ";", "%"];
+impl SourceLine<'_> {
+    fn content(&self) -> &str {
+        &self.text[self.content.clone()]
+    }
 
-    for prefix in prefixes {
-        if let Some(rest) = trimmed.strip_prefix(prefix) {
-            // calculate where the prefix starts in original line
-            let prefix_start = line.len() - trimmed.len();
-            let prefix_end = prefix_start + prefix.len();
-            return Some((rest, &line[..prefix_end]));
+    /// The content as it reads - a `#[doc = "..."]` string with its escapes
+    /// resolved, so a mantra is the same text there as in markdown
+    fn resolved(&self) -> (Cow<'_, str>, Vec<Escape>) {
+        match self.escaped {
+            true => unescape(self.content()),
+            false => (Cow::Borrowed(self.content()), Vec::new()),
         }
     }
+}
 
-    None
+/// A string escape resolved in a line's content (`\"` read as `"`)
+#[derive(Debug, Clone, Copy)]
+struct Escape {
+    /// Offset just past the character it resolved to, in the resolved text
+    end: usize,
+    /// How many more bytes, and characters, it takes in the source
+    extra_bytes: usize,
+    extra_columns: usize,
 }
 
-/// Check if a line is a comment-only line (just the comment prefix, maybe with whitespace)
-fn is_comment_only_line(line: &str) -> bool {
-    if let Some((rest, _)) = strip_comment_prefix(line) {
-        rest.trim().is_empty()
-    } else {
-        false
+/// Resolve the escapes of a Rust string literal's inside - left as it is if
+/// one of them is malformed
+fn unescape(literal: &str) -> (Cow<'_, str>, Vec<Escape>) {
+    if !literal.contains('\\') {
+        return (Cow::Borrowed(literal), Vec::new());
+    }
+    let mut text = String::with_capacity(literal.len());
+    let mut escapes = Vec::new();
+    let mut chars = literal.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        let resolved = match chars.next().map(|(_, c)| c) {
+            Some('n') => Some('\n'),
+            Some('r') => Some('\r'),
+            Some('t') => Some('\t'),
+            Some('0') => Some('\0'),
+            Some(c @ ('\\' | '"' | '\'')) => Some(c),
+            Some('x') => {
+                let digits: String = chars.by_ref().take(2).map(|(_, c)| c).collect();
+                u8::from_str_radix(&digits, 16).ok().filter(u8::is_ascii).map(char::from)
+            }
+            Some('u') if chars.next_if(|&(_, c)| c == '{').is_some() => {
+                let digits: String = chars.by_ref().map(|(_, c)| c).take_while(|&c| c != '}').collect();
+                u32::from_str_radix(&digits.replace('_', ""), 16).ok().and_then(char::from_u32)
+            }
+            _ => None,
+        };
+        let Some(resolved) = resolved else {
+            return (Cow::Borrowed(literal), Vec::new());
+        };
+        let source = &literal[start..chars.peek().map_or(literal.len(), |&(end, _)| end)];
+        text.push(resolved);
+        escapes.push(Escape {
+            end: text.len(),
+            extra_bytes: source.len() - resolved.len_utf8(),
+            extra_columns: source.chars().count() - 1,
+        });
     }
+    (Cow::Owned(text), escapes)
 }
 
-fn extract_paragraphs(lines: &[&str], skip_lines: &[bool]) -> Vec<Paragraph> {
+/// Split file content into lines, locating code blocks, line comments and
+/// block comments (which only start at the beginning of a line)
+fn scan_lines<'a>(content: &'a str, syntax: &'a CommentSyntax) -> Vec<SourceLine<'a>> {
+    let mut lines = Vec::new();
+    let mut in_code_block = false;
+    let mut open_block: Option<&BlockComment> = None;
+    // a string opened mid-line with a docstring quote (`x = """`) - its
+    // lines, and the quote closing it, are code rather than a comment
+    let mut open_string: Option<&str> = None;
+    let mut offset = 0;
+
+    for raw in content.split_inclusive('\n') {
+        let text = raw.strip_suffix('\n').unwrap_or(raw);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        let line_offset = offset;
+        offset += raw.len();
+
+        let indent = text.len() - text.trim_start().len();
+        let mut line = SourceLine {
+            text,
+            offset: line_offset,
+            content: 0..text.len(),
+            marker: None,
+            in_code_block: false,
+            escaped: false,
+        };
+
+        if let Some(quote) = open_string {
+            if text.matches(quote).count() % 2 == 1 {
+                open_string = None;
+            }
+        } else if let Some(block) = open_block {
+            // continuation of a block comment
+            let mut start = indent;
+            if let Some(deco) = block.continuation() {
+                if text[start..].starts_with(deco) && !text[start..].starts_with(&block.close) {
+                    start += deco.len_utf8();
+                }
+            }
+            let end = match text[start..].find(&block.close) {
+                Some(pos) => {
+                    open_block = None;
+                    start + text[start..start + pos].trim_end().len()
+                }
+                None => text.len(),
+            };
+            line.content = start..end;
+            line.marker = Some(&block.open);
+        } else if text.trim().starts_with("```") {
+            in_code_block = !in_code_block;
+            line.in_code_block = true;
+        } else if in_code_block {
+            line.in_code_block = true;
+        } else if let Some(block) = syntax.block.iter().find(|b| text[indent..].starts_with(&b.open)) {
+            // start of a block comment - it may also close on this line
+            let start = indent + block.open.len();
+            let end = match text[start..].find(&block.close) {
+                Some(pos) => start + text[start..start + pos].trim_end().len(),
+                None => {
+                    open_block = Some(block);
+                    text.len()
+                }
+            };
+            line.content = start..end;
+            line.marker = Some(&block.open);
+        } else if let Some((value, opener, escaped)) = syntax.doc_attribute(text) {
+            line.content = value;
+            line.marker = Some(opener);
+            line.escaped = escaped;
+        } else if let Some((rest, prefix)) = syntax.strip_line_prefix(text) {
+            line.content = text.len() - rest.len()..text.len();
+            line.marker = Some(prefix);
+        } else {
+            let unclosed = |b: &&BlockComment| b.open == b.close && text.matches(b.open.as_str()).count() % 2 == 1;
+            open_string = syntax.block.iter().find(unclosed).map(|b| b.close.as_str());
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// The block comment holding the line at byte `at` of `content`, if any, and
+/// whether it is still open at the end of that line
+pub fn block_comment_at<'a>(content: &str, at: usize, syntax: &'a CommentSyntax) -> Option<(&'a BlockComment, bool)> {
+    let line = scan_lines(content, syntax).into_iter().take_while(|l| l.offset <= at).last()?;
+    let block = syntax.block.iter().find(|b| line.marker == Some(b.open.as_str()))?;
+    Some((block, line.content.end == line.text.len()))
+}
+
+#[derive(Debug)]
+struct Paragraph {
+    /// Indices into the file's source lines
+    lines: Vec<usize>,
+    /// If set, this paragraph is deprecated - the index of the preceding `tyakta:` line
+    tyakta: Option<usize>,
+    /// If set, this paragraph is attributed to a shastra (from preceding `shastra: name` line)
+    shastra: Option<String>,
+    /// If set, this paragraph refutes a bhasya from another shastra (from preceding `khandita: name` line)
+    khandita: Option<String>,
+}
+
+fn extract_paragraphs(lines: &[SourceLine]) -> Vec<Paragraph> {
     let mut paragraphs = Vec::new();
-    let mut current_lines: Vec<(usize, String)> = Vec::new();
+    let mut current_lines: Vec<usize> = Vec::new();
     let mut in_quote_block = false;
-    let mut is_deprecated = false;
+    let mut current_tyakta: Option<usize> = None;
     let mut current_shastra: Option<String> = None;
     let mut pending_shastra: Option<String> = None;
     let mut current_khandita: Option<String> = None;
     let mut pending_khandita: Option<String> = None;
-    let mut pending_tyakta = false;
+    let mut pending_tyakta: Option<usize> = None;
     let mut current_comment_prefix: Option<String> = None;
 
     for (i, line) in lines.iter().enumerate() {
-        let line_num = i + 1;
-
-        if skip_lines[i] {
+        if line.in_code_block {
             // end current paragraph if any
             if !current_lines.is_empty() {
-                let text = current_lines.iter().map(|(_, l)| l.as_str()).collect::<Vec<_>>().join("\n");
                 paragraphs.push(Paragraph {
-                    text,
                     lines: std::mem::take(&mut current_lines),
-                    is_deprecated,
+                    tyakta: current_tyakta.take(),
                     shastra: current_shastra.take(),
                     khandita: current_khandita.take(),
                 });
                 in_quote_block = false;
-                is_deprecated = false;
+                current_tyakta = None;
                 current_comment_prefix = None;
             }
             pending_shastra = None;
             pending_khandita = None;
-            pending_tyakta = false;
+            pending_tyakta = None;
             continue;
         }
 
-        // try to strip comment prefix for source code files
-        let (content, comment_prefix) = if let Some((rest, prefix)) = strip_comment_prefix(line) {
-            (rest.trim_start(), Some(prefix))
-        } else {
-            (line.trim_start(), None)
-        };
+        // comment markers were stripped by scan_lines
+        let content = line.content().trim_start();
+        let comment_prefix = line.marker;
 
         // check for quote line (just > now, not >>)
         let is_quote_line = content.starts_with('>');
-        let is_empty = line.trim().is_empty() || is_comment_only_line(line);
+        let is_empty = content.trim().is_empty();
 
         // check for `shastra: name`, `khandita: name`, or `tyakta:` pattern (must be alone on its line)
         if !in_quote_block && !is_quote_line && !is_empty {
@@ -301,7 +715,7 @@ fn extract_paragraphs(lines: &[&str], skip_lines: &[bool]) -> Vec<Paragraph> {
                     // this is a shastra attribution line - remember it for next quote block
                     pending_shastra = Some(shastra_name.to_string());
                     pending_khandita = None; // these are mutually exclusive
-                    pending_tyakta = false;
+                    pending_tyakta = None;
                     current_comment_prefix = comment_prefix.map(|s| s.to_string());
                     continue; // don't include this line in any paragraph
                 }
@@ -312,14 +726,14 @@ fn extract_paragraphs(lines: &[&str], skip_lines: &[bool]) -> Vec<Paragraph> {
                     // this is a khandita (refutation) line - remember it for next quote block
                     pending_khandita = Some(khandita_name.to_string());
                     pending_shastra = None; // these are mutually exclusive
-                    pending_tyakta = false;
+                    pending_tyakta = None;
                     current_comment_prefix = comment_prefix.map(|s| s.to_string());
                     continue; // don't include this line in any paragraph
                 }
             }
             if content.starts_with("tyakta:") {
                 // this is a tyakta (deprecation) line - remember it for next quote block
-                pending_tyakta = true;
+                pending_tyakta = Some(i);
                 pending_shastra = None; // these are mutually exclusive
                 pending_khandita = None;
                 current_comment_prefix = comment_prefix.map(|s| s.to_string());
@@ -337,21 +751,19 @@ fn extract_paragraphs(lines: &[&str], skip_lines: &[bool]) -> Vec<Paragraph> {
             };
 
             if is_quote_line && same_comment_style {
-                current_lines.push((line_num, line.to_string()));
+                current_lines.push(i);
             } else {
                 // non-quote line (including empty lines) ends the quote block
                 // in markdown, blank lines between > blocks create separate blocks
-                let text = current_lines.iter().map(|(_, l)| l.as_str()).collect::<Vec<_>>().join("\n");
                 paragraphs.push(Paragraph {
-                    text,
                     lines: std::mem::take(&mut current_lines),
-                    is_deprecated,
+                    tyakta: current_tyakta.take(),
                     shastra: current_shastra.take(),
                     khandita: current_khandita.take(),
                 });
 
                 in_quote_block = false;
-                is_deprecated = false;
+                current_tyakta = None;
                 current_comment_prefix = None;
 
                 // check for prefix lines that end the quote block
@@ -360,7 +772,7 @@ fn extract_paragraphs(lines: &[&str], skip_lines: &[bool]) -> Vec<Paragraph> {
                     if !shastra_name.is_empty() {
                         pending_shastra = Some(shastra_name.to_string());
                         pending_khandita = None;
-                        pending_tyakta = false;
+                        pending_tyakta = None;
                         current_comment_prefix = comment_prefix.map(|s| s.to_string());
                         continue;
                     }
@@ -370,13 +782,13 @@ fn extract_paragraphs(lines: &[&str], skip_lines: &[bool]) -> Vec<Paragraph> {
                     if !khandita_name.is_empty() {
                         pending_khandita = Some(khandita_name.to_string());
                         pending_shastra = None;
-                        pending_tyakta = false;
+                        pending_tyakta = None;
                         current_comment_prefix = comment_prefix.map(|s| s.to_string());
                         continue;
                     }
                 }
                 if content.starts_with("tyakta:") {
-                    pending_tyakta = true;
+                    pending_tyakta = Some(i);
                     pending_shastra = None;
                     pending_khandita = None;
                     current_comment_prefix = comment_prefix.map(|s| s.to_string());
@@ -386,15 +798,14 @@ fn extract_paragraphs(lines: &[&str], skip_lines: &[bool]) -> Vec<Paragraph> {
                 // if this line is a quote line, start a new quote block
                 if is_quote_line {
                     in_quote_block = true;
-                    is_deprecated = pending_tyakta;
-                    pending_tyakta = false;
+                    current_tyakta = pending_tyakta.take();
                     current_shastra = pending_shastra.take();
                     current_khandita = pending_khandita.take();
                     current_comment_prefix = comment_prefix.map(|s| s.to_string());
-                    current_lines.push((line_num, line.to_string()));
+                    current_lines.push(i);
                 } else if !is_empty {
                     // start new paragraph with this line if not empty
-                    current_lines.push((line_num, line.to_string()));
+                    current_lines.push(i);
                 }
             }
         } else {
@@ -402,11 +813,9 @@ fn extract_paragraphs(lines: &[&str], skip_lines: &[bool]) -> Vec<Paragraph> {
             if is_empty {
                 // empty line ends paragraph
                 if !current_lines.is_empty() {
-                    let text = current_lines.iter().map(|(_, l)| l.as_str()).collect::<Vec<_>>().join("\n");
                     paragraphs.push(Paragraph {
-                        text,
                         lines: std::mem::take(&mut current_lines),
-                        is_deprecated: false,
+                        tyakta: None,
                         shastra: None,
                         khandita: None,
                     });
@@ -414,35 +823,32 @@ fn extract_paragraphs(lines: &[&str], skip_lines: &[bool]) -> Vec<Paragraph> {
                 // empty line also clears pending state
                 pending_shastra = None;
                 pending_khandita = None;
-                pending_tyakta = false;
+                pending_tyakta = None;
                 current_comment_prefix = None;
             } else if is_quote_line {
                 // starting a quote block
                 if !current_lines.is_empty() {
                     // end previous non-quote paragraph
-                    let text = current_lines.iter().map(|(_, l)| l.as_str()).collect::<Vec<_>>().join("\n");
                     paragraphs.push(Paragraph {
-                        text,
                         lines: std::mem::take(&mut current_lines),
-                        is_deprecated: false,
+                        tyakta: None,
                         shastra: None,
                         khandita: None,
                     });
                 }
                 in_quote_block = true;
-                is_deprecated = pending_tyakta;
-                pending_tyakta = false;
+                current_tyakta = pending_tyakta.take();
                 current_shastra = pending_shastra.take();
                 current_khandita = pending_khandita.take();
                 current_comment_prefix = comment_prefix.map(|s| s.to_string());
-                current_lines.push((line_num, line.to_string()));
+                current_lines.push(i);
             } else {
                 // regular line
-                current_lines.push((line_num, line.to_string()));
+                current_lines.push(i);
                 // regular line clears pending state
                 pending_shastra = None;
                 pending_khandita = None;
-                pending_tyakta = false;
+                pending_tyakta = None;
                 current_comment_prefix = None;
             }
         }
@@ -450,11 +856,9 @@ fn extract_paragraphs(lines: &[&str], skip_lines: &[bool]) -> Vec<Paragraph> {
 
     // don't forget the last paragraph
     if !current_lines.is_empty() {
-        let text = current_lines.iter().map(|(_, l)| l.as_str()).collect::<Vec<_>>().join("\n");
         paragraphs.push(Paragraph {
-            text,
             lines: current_lines,
-            is_deprecated,
+            tyakta: current_tyakta,
             shastra: current_shastra,
             khandita: current_khandita,
         });
@@ -463,61 +867,42 @@ fn extract_paragraphs(lines: &[&str], skip_lines: &[bool]) -> Vec<Paragraph> {
     paragraphs
 }
 
-/// Strip comment prefix and quote markers from a line
-fn strip_comment_and_quote(line: &str) -> &str {
-    // first strip comment prefix if present
-    let after_comment = if let Some((rest, _)) = strip_comment_prefix(line) {
-        rest.trim_start()
-    } else {
-        line.trim_start()
-    };
-
-    // then strip > quote prefix
-    after_comment.strip_prefix('>')
-        .unwrap_or(after_comment)
+/// Strip quote markers from a line's comment content
+fn strip_quote(content: &str) -> &str {
+    let content = content.trim_start();
+    content.strip_prefix('>')
+        .unwrap_or(content)
         .trim_start()
 }
 
-/// Check if paragraph text starts with a quote marker (after stripping comment prefix)
-fn is_quote_paragraph(text: &str) -> bool {
-    if let Some(first_line) = text.lines().next() {
-        let content = if let Some((rest, _)) = strip_comment_prefix(first_line) {
-            rest.trim_start()
-        } else {
-            first_line.trim_start()
-        };
-        content.starts_with('>')
-    } else {
-        false
-    }
+/// Check if a paragraph starts with a quote marker (after stripping comment markers)
+fn is_quote_paragraph(para: &Paragraph, lines: &[SourceLine]) -> bool {
+    para.lines
+        .first()
+        .is_some_and(|&i| lines[i].content().trim_start().starts_with('>'))
 }
 
-fn parse_paragraph(para: &Paragraph, file_name: &str, repo: &mut Repository) {
+fn parse_paragraph(para: &Paragraph, lines: &[SourceLine], file_name: &str, file: &mut ParsedFile) {
     // check if this is a quote block (bhasyas require quote blocks)
-    let is_quote_block = is_quote_paragraph(&para.text);
+    let is_quote_block = is_quote_paragraph(para, lines);
 
     if is_quote_block {
-        // strip comment prefix and > from paragraph text
-        let unquoted_para: String = para.text
-            .lines()
-            .map(strip_comment_and_quote)
-            .collect::<Vec<_>>()
-            .join("\n");
+        // strip comment markers and > from paragraph text
+        let unquoted = ParagraphText::new(para, lines, strip_quote);
 
         // first pass: check if this quote block contains any mula mantras
-        let has_mula_mantra = para.lines.iter().any(|(_, line)| {
-            let unquoted = strip_comment_and_quote(line);
-            unquoted.starts_with("**^")
+        let has_mula_mantra = para.lines.iter().any(|&i| {
+            strip_quote(lines[i].content()).starts_with("**^")
         });
 
         // only create a bhasya if it contains at least one mula mantra
         let bhasya_index = if has_mula_mantra {
-            let start_line = para.lines.first().map(|(n, _)| *n).unwrap_or(0);
-            let idx = repo.bhasyas.len();
+            let start_line = para.lines.first().map(|i| i + 1).unwrap_or(0);
+            let idx = file.bhasyas.len();
 
             // determine the kind based on paragraph attributes
-            let kind = if para.is_deprecated {
-                BhasyaKind::Tyakta
+            let kind = if let Some(tyakta_line) = para.tyakta {
+                BhasyaKind::Tyakta(parse_tyakta_line(tyakta_line, lines, file_name, file))
             } else if let Some(ref shastra) = para.shastra {
                 BhasyaKind::Uddhrit(shastra.clone())
             } else if let Some(ref khandita) = para.khandita {
@@ -526,240 +911,319 @@ fn parse_paragraph(para: &Paragraph, file_name: &str, repo: &mut Repository) {
                 BhasyaKind::Mula
             };
 
-            repo.bhasyas.push(Bhasya {
-                paragraph: unquoted_para.clone(),
+            // the block runs from the start of its first line to the end of its last
+            let span = match (para.lines.first(), para.lines.last()) {
+                (Some(&first), Some(&last)) => Span {
+                    start: Position { line: first + 1, column: 1, byte: lines[first].offset },
+                    end: Position {
+                        line: last + 1,
+                        column: lines[last].text.chars().count() + 1,
+                        byte: lines[last].offset + lines[last].text.len(),
+                    },
+                },
+                _ => Span::default(),
+            };
+
+            file.bhasyas.push(Bhasya {
+                paragraph: unquoted.text.clone(),
                 file: file_name.to_string(),
                 line: start_line,
+                span,
                 kind,
+                mulas: Vec::new(),
+                anusrits: Vec::new(),
             });
             Some(idx)
         } else {
             None
         };
 
-        // second pass: parse each line for mantras and references
-        for (line_num, line) in &para.lines {
-            let unquoted_line = strip_comment_and_quote(line);
-            parse_line_in_bhasya(
-                unquoted_line,
-                file_name,
-                *line_num,
-                repo,
-                bhasya_index,
-            );
-        }
+        // second pass: parse the whole paragraph for mantras and references
+        parse_inline(&unquoted, file_name, file, bhasya_index, true);
     } else {
         // not a quote block - only parse for anusrits, not mula mantras
-        for (line_num, line) in &para.lines {
-            let content = if let Some((rest, _)) = strip_comment_prefix(line) {
-                rest
-            } else {
-                line.as_str()
-            };
-            parse_line_for_anusrits(content, file_name, *line_num, repo, None);
+        let text = ParagraphText::new(para, lines, |content| content);
+        parse_inline(&text, file_name, file, None, false);
+    }
+}
+
+/// Paragraph lines with their markers stripped, joined with `\n` so that
+/// mantras and anusrits can span soft line breaks
+struct ParagraphText {
+    text: String,
+    /// Where each stripped line starts, in `text` and in the source file
+    line_starts: Vec<LineStart>,
+    /// Escapes resolved in `text`, which are longer in the source
+    escapes: Vec<Escape>,
+}
+
+struct LineStart {
+    /// Offset of the line in the joined `text`
+    offset: usize,
+    /// Position of the first character after the stripped markers
+    position: Position,
+}
+
+impl ParagraphText {
+    /// `strip` is applied to each line's comment content and must only remove a prefix
+    fn new(para: &Paragraph, lines: &[SourceLine], strip: impl Fn(&str) -> &str) -> Self {
+        let mut text = String::new();
+        let mut line_starts = Vec::with_capacity(para.lines.len());
+        let mut escapes = Vec::new();
+        for &i in &para.lines {
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            let line = &lines[i];
+            let (resolved, line_escapes) = line.resolved();
+            let content = strip(&resolved);
+            let stripped = resolved.len() - content.len();
+            let stripped_source: usize =
+                stripped + line_escapes.iter().filter(|e| e.end <= stripped).map(|e| e.extra_bytes).sum::<usize>();
+            let prefix = &line.text[..line.content.start + stripped_source];
+            for escape in line_escapes.iter().filter(|e| e.end > stripped) {
+                escapes.push(Escape { end: text.len() + escape.end - stripped, ..*escape });
+            }
+            line_starts.push(LineStart {
+                offset: text.len(),
+                position: Position {
+                    line: i + 1,
+                    column: prefix.chars().count() + 1,
+                    byte: line.offset + prefix.len(),
+                },
+            });
+            text.push_str(content);
+        }
+        ParagraphText { text, line_starts, escapes }
+    }
+
+    /// Source position of the given offset in `text`
+    fn position_at(&self, offset: usize) -> Position {
+        let idx = self.line_starts.partition_point(|l| l.offset <= offset).saturating_sub(1);
+        let start = &self.line_starts[idx];
+        let within = &self.text[start.offset..offset];
+        let escapes = self.escapes.iter().filter(|e| e.end > start.offset && e.end <= offset);
+        let (bytes, columns) = escapes.fold((0, 0), |(b, c), e| (b + e.extra_bytes, c + e.extra_columns));
+        Position {
+            line: start.position.line,
+            column: start.position.column + within.chars().count() + columns,
+            byte: start.position.byte + within.len() + bytes,
+        }
+    }
+
+    fn span(&self, range: std::ops::Range<usize>) -> Span {
+        Span {
+            start: self.position_at(range.start),
+            end: self.position_at(range.end),
         }
     }
 }
 
-/// Parse a line inside a bhasya (quote block) for mula mantras and anusrits
-fn parse_line_in_bhasya(
-    line: &str,
+/// Parse paragraph text for `**^mula^**` mantras (only if `allow_mula`, and only
+/// at the start of a line) and `_| anusrit |_` references
+fn parse_inline(
+    para: &ParagraphText,
     file_name: &str,
-    line_num: usize,
-    repo: &mut Repository,
+    file: &mut ParsedFile,
     bhasya_index: Option<usize>,
+    allow_mula: bool,
 ) {
-    let mut chars = line.chars().peekable();
-    let mut in_backtick = false;
-    let mut position = 0usize;
-
-    while let Some(c) = chars.next() {
-        // skip content inside backticks (inline code)
-        if c == '`' {
-            in_backtick = !in_backtick;
-            position += 1;
-            continue;
-        }
-        if in_backtick {
-            position += 1;
+    let text = para.text.as_str();
+    let mut pos = 0;
+
+    while pos < text.len() {
+        let rest = &text[pos..];
+        let at_line_start = pos == 0 || text.as_bytes()[pos - 1] == b'\n';
+
+        // skip content inside backticks (inline code), unless the backtick is unmatched
+        if let Some(code) = rest.strip_prefix('`') {
+            pos += code.find('`').map_or(1, |end| end + 2);
             continue;
         }
 
         // **^mantra^** - mula mantra syntax
-        // only at the START of a line (position 0) - mid-line occurrences are ignored
-        if c == '*' && chars.peek() == Some(&'*') {
-            chars.next(); // consume second *
-            if chars.peek() == Some(&'^') {
-                chars.next(); // consume ^
-                let mut mantra_text = String::new();
-
-                for c in chars.by_ref() {
-                    if c == '^' {
-                        break;
-                    }
-                    mantra_text.push(c);
-                }
-
-                // consume closing **
-                if chars.peek() == Some(&'*') {
-                    chars.next();
-                    if chars.peek() == Some(&'*') {
-                        chars.next();
-                    }
+        // only at the START of a line - mid-line occurrences are ignored
+        if let Some(inner) = rest.strip_prefix("**^") {
+            let Some(end) = inner.find('^') else {
+                pos += 3;
+                continue;
+            };
+            let start = pos;
+            pos += 3 + end + 1;
+            // consume closing **
+            for _ in 0..2 {
+                if text[pos..].starts_with('*') {
+                    pos += 1;
                 }
-
-                let mantra_text = mantra_text.trim().to_string();
-                // only create mula mantra if at start of line (position 0)
-                if !mantra_text.is_empty() && position == 0 {
-                    if let Some(idx) = bhasya_index {
-                        // add to mantras with mula_bhasyas
-                        let entry = repo.mantras.entry(mantra_text.clone()).or_default();
-                        entry.mula_bhasyas.push(idx);
-
-                        // set first definition location if not set
-                        if entry.file.is_empty() {
-                            entry.file = file_name.to_string();
-                            entry.line = line_num;
-                        }
-
-                        // mark as explained if this is a Mula bhasya
-                        let is_mula = repo.bhasyas.get(idx)
-                            .map(|b| matches!(b.kind, BhasyaKind::Mula))
-                            .unwrap_or(false);
-                        if is_mula {
-                            entry.has_explanation = true;
-                        }
-                    }
+            }
+            // only an accepted mula's spelling is recorded - prose markers are not mantras
+            let raw = &inner[..end];
+            if allow_mula && at_line_start && !normalize_mantra(raw).is_empty() {
+                if let Some(idx) = bhasya_index {
+                    let span = para.span(start..pos);
+                    let mantra_text = canonical_mantra(raw, file_name, span, file);
+                    file.bhasyas[idx].mulas.push(Mula { mantra_text, span });
                 }
-                continue;
             }
-            position += 2; // already consumed two *
             continue;
         }
-        position += 1;
 
         // _| mantra |_ - anusrit syntax
-        if c == '_' && chars.peek() == Some(&'|') {
-            chars.next(); // consume |
-            let mut ref_text = String::new();
-
-            loop {
-                match chars.next() {
-                    Some('|') if chars.peek() == Some(&'_') => {
-                        chars.next(); // consume _
-                        break;
-                    }
-                    Some(c) => ref_text.push(c),
-                    None => break,
-                }
-            }
+        // (opening delimiter matched char by char so this source doesn't contain it)
+        if rest.starts_with('_') && rest[1..].starts_with('|') {
+            let inner = &rest[2..];
+            let Some(end) = inner.find("|_") else {
+                pos += 2;
+                continue;
+            };
+            let span = para.span(pos..pos + 2 + end + 2);
+            let ref_text = canonical_mantra(&inner[..end], file_name, span, file);
+            pos += 2 + end + 2;
 
-            let ref_text = ref_text.trim().to_string();
-            if !ref_text.is_empty() {
-                // check for @shastra suffix
-                let mut shastra_ref = None;
-                if chars.peek() == Some(&'@') {
-                    chars.next(); // consume @
-                    let mut shastra_name = String::new();
-                    for c in chars.by_ref() {
-                        if c.is_alphanumeric() || c == '-' || c == '_' {
-                            shastra_name.push(c);
-                        } else {
-                            break;
-                        }
-                    }
-                    if !shastra_name.is_empty() {
-                        shastra_ref = Some(shastra_name);
-                    }
-                }
+            // check for @shastra suffix
+            let (shastra_ref, suffix_len) = shastra_suffix(&text[pos..]);
+            pos += suffix_len;
 
-                // if inside a bhasya, add to mantras.anusrit_bhasyas; otherwise to anusrits
+            if !ref_text.is_empty() {
+                let anusrit = Anusrit {
+                    mantra_text: ref_text,
+                    file: file_name.to_string(),
+                    line: span.start.line,
+                    span,
+                    shastra: shastra_ref,
+                };
+                // if inside a bhasya, keep it with the bhasya; otherwise to anusrits
                 if let Some(idx) = bhasya_index {
-                    let entry = repo.mantras.entry(ref_text).or_default();
-                    entry.anusrit_bhasyas.push(idx);
+                    file.bhasyas[idx].anusrits.push(anusrit);
                 } else {
-                    repo.anusrits.push(Anusrit {
-                        mantra_text: ref_text,
-                        file: file_name.to_string(),
-                        line: line_num,
-                        shastra: shastra_ref,
-                    });
+                    file.anusrits.push(anusrit);
                 }
             }
+            continue;
         }
+
+        pos += rest.chars().next().map_or(1, char::len_utf8);
     }
 }
 
-/// Parse a line outside quote blocks for anusrits only
-fn parse_line_for_anusrits(
-    line: &str,
-    file_name: &str,
-    line_num: usize,
-    repo: &mut Repository,
-    bhasya_index: Option<usize>,
-) {
-    let mut chars = line.chars().peekable();
-    let mut in_backtick = false;
+/// The `@shastra-name` after an anusrit, and how many bytes it takes up
+fn shastra_suffix(text: &str) -> (Option<String>, usize) {
+    let Some(after_at) = text.strip_prefix('@') else {
+        return (None, 0);
+    };
+    let name_len = after_at
+        .find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_'))
+        .unwrap_or(after_at.len());
+    let name = (name_len > 0).then(|| after_at[..name_len].to_string());
+    (name, 1 + name_len)
+}
 
-    while let Some(c) = chars.next() {
-        if c == '`' {
-            in_backtick = !in_backtick;
-            continue;
-        }
-        if in_backtick {
-            continue;
+// _| tyakta can name its replacement |_
+/// Parse what follows `tyakta:` - an optional `YYYY-MM-DD` date, an optional
+/// `-> _| replacement |_` and free text giving the reason, in that order
+/// (text that is not a date before the arrow is part of the reason)
+fn parse_tyakta_line(index: usize, lines: &[SourceLine], file_name: &str, file: &mut ParsedFile) -> TyaktaNote {
+    let line = Paragraph { lines: vec![index], tyakta: None, shastra: None, khandita: None };
+    let para = ParagraphText::new(&line, lines, |content| {
+        let content = content.trim_start();
+        content.strip_prefix("tyakta:").unwrap_or(content)
+    });
+    let text = para.text.as_str();
+    let mut note = TyaktaNote::default();
+
+    // the date is the first word, but a malformed one must not hide the replacement
+    let (head, mut rest) = match text.find("->") {
+        Some(arrow) => (&text[..arrow], &text[arrow..]),
+        None => (text, ""),
+    };
+    let mut head = head.trim();
+    if let Some(word) = head.split_whitespace().next() {
+        if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+            note.date = Some(date);
+            head = head[word.len()..].trim_start();
         }
+    }
 
-        // _| mantra |_ - anusrit syntax
-        if c == '_' && chars.peek() == Some(&'|') {
-            chars.next(); // consume |
-            let mut ref_text = String::new();
-
-            loop {
-                match chars.next() {
-                    Some('|') if chars.peek() == Some(&'_') => {
-                        chars.next(); // consume _
-                        break;
-                    }
-                    Some(c) => ref_text.push(c),
-                    None => break,
-                }
-            }
-
-            let ref_text = ref_text.trim().to_string();
-            if !ref_text.is_empty() {
-                // check for @shastra suffix
-                let mut shastra_ref = None;
-                if chars.peek() == Some(&'@') {
-                    chars.next(); // consume @
-                    let mut shastra_name = String::new();
-                    for c in chars.by_ref() {
-                        if c.is_alphanumeric() || c == '-' || c == '_' {
-                            shastra_name.push(c);
-                        } else {
-                            break;
-                        }
-                    }
-                    if !shastra_name.is_empty() {
-                        shastra_ref = Some(shastra_name);
-                    }
-                }
-
-                // if inside a bhasya, add to mantras.anusrit_bhasyas; otherwise to anusrits
-                if let Some(idx) = bhasya_index {
-                    let entry = repo.mantras.entry(ref_text).or_default();
-                    entry.anusrit_bhasyas.push(idx);
-                } else {
-                    repo.anusrits.push(Anusrit {
-                        mantra_text: ref_text,
+    if let Some(arrow) = rest.strip_prefix("->") {
+        rest = arrow.trim_start();
+        // an anusrit, matched char by char like in parse_inline
+        if rest.starts_with('_') && rest[1..].starts_with('|') {
+            if let Some(end) = rest[2..].find("|_") {
+                let start = text.len() - rest.len();
+                let span = para.span(start..start + 2 + end + 2);
+                let mantra_text = canonical_mantra(&rest[2..2 + end], file_name, span, file);
+                let (shastra, suffix_len) = shastra_suffix(&rest[2 + end + 2..]);
+                rest = &rest[2 + end + 2 + suffix_len..];
+                if !mantra_text.is_empty() {
+                    note.replacement = Some(Anusrit {
+                        mantra_text,
                         file: file_name.to_string(),
-                        line: line_num,
-                        shastra: shastra_ref,
+                        line: span.start.line,
+                        span,
+                        shastra,
                     });
                 }
             }
         }
     }
+
+    let reason = [head, rest.trim().trim_start_matches(['-', ':', '—']).trim()]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if !reason.is_empty() {
+        note.reason = Some(reason);
+    }
+    note
+}
+
+// _| mantra text is normalized |_
+/// Canonical form of mantra text: unicode NFC, invisible zero-width characters
+/// removed, and every whitespace run (including non-breaking spaces and line
+/// breaks) collapsed to a single space
+pub fn normalize_mantra(text: &str) -> String {
+    text.nfc()
+        .filter(|c| !matches!(c, '\u{200b}' | '\u{feff}'))
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Describe why a mantra spelling is not canonical (empty if it is)
+pub fn spelling_issues(text: &str) -> Vec<&'static str> {
+    let mut issues = Vec::new();
+    if !is_nfc(text) {
+        issues.push("not unicode NFC");
+    }
+    if text.contains(['\u{200b}', '\u{feff}']) {
+        issues.push("zero-width character");
+    }
+    if text.chars().any(|c| c.is_whitespace() && c != ' ') {
+        issues.push("whitespace other than plain space");
+    }
+    if text.contains("  ") {
+        issues.push("repeated spaces");
+    }
+    issues
+}
+
+/// Normalize mantra text found while parsing, recording non-canonical spellings
+/// (a soft line break inside a wrapped mantra is not a spelling issue)
+fn canonical_mantra(raw: &str, file_name: &str, span: Span, file: &mut ParsedFile) -> String {
+    let raw = raw.trim().split('\n').map(str::trim).collect::<Vec<_>>().join(" ");
+    let canonical = normalize_mantra(&raw);
+    if !canonical.is_empty() && canonical != raw {
+        file.non_canonical.push(NonCanonicalSpelling {
+            raw,
+            canonical: canonical.clone(),
+            file: file_name.to_string(),
+            line: span.start.line,
+            span,
+        });
+    }
+    canonical
 }
 
 // _| mantra commentary can be in same para |_ - mark mantras as explained if they have nearby commentary
@@ -770,6 +1234,89 @@ fn mark_explained_mantras(_repo: &mut Repository) {
     // The has_explanation field is already set in parse_line.
 }
 
+/// Include / exclude globs from config, compiled
+struct FileFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl FileFilter {
+    fn new(config: &Config) -> Result<Self, String> {
+        let include = if config.include.is_empty() {
+            None
+        } else {
+            Some(build_globs(&config.include)?)
+        };
+        Ok(FileFilter {
+            include,
+            exclude: build_globs(&config.exclude)?,
+        })
+    }
+
+    /// Whether a file (path relative to the repository root) should be scanned
+    fn accepts(&self, relative: &Path) -> bool {
+        self.include.as_ref().is_none_or(|set| set.is_match(relative))
+            && !self.exclude.is_match(relative)
+    }
+}
+
+fn build_globs(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .map_err(|e| format!("invalid glob '{}' in .vyasa/config.json: {}", pattern, e))?;
+        builder.add(glob);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// Whether the walk in `Repository::parse` from `root` skips the file at
+/// `relative`: a hidden file or folder on the way, a built site, or a path
+/// ignored by `.gitignore`, `.ignore` or `.git/info/exclude` at any level -
+/// the deepest rule that matches decides
+fn walk_skips(root: &Path, relative: &Path) -> bool {
+    let names: Vec<_> = relative.components().collect();
+    let mut dir = root.to_path_buf();
+    let mut ignores = Vec::new();
+    for (i, name) in names.iter().enumerate() {
+        let Component::Normal(name) = name else {
+            return false;
+        };
+        if name.to_string_lossy().starts_with('.') {
+            return true;
+        }
+        ignores.push(ignore_files(&dir));
+        let path = dir.join(name);
+        let is_dir = i + 1 < names.len();
+        let matched = ignores.iter().rev().map(|ignore| ignore.matched(&path, is_dir)).find(|m| !m.is_none());
+        if matched.is_some_and(|m| m.is_ignore()) || (is_dir && is_site(&path)) {
+            return true;
+        }
+        dir = path;
+    }
+    false
+}
+
+/// The ignore rules of one folder - `.ignore` over `.gitignore` over
+/// `.git/info/exclude`, as the walk in `Repository::parse` reads them
+fn ignore_files(dir: &Path) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(dir);
+    for file in [".git/info/exclude", ".gitignore", ".ignore"] {
+        // a missing file adds nothing; the last rule that matches wins
+        let _ = builder.add(dir.join(file));
+    }
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+/// Marks a folder `vyasa build` wrote - its pages repeat the shastra's
+/// mantras, so it is never scanned
+pub const SITE_MARKER: &str = ".vyasa-site";
+
+/// Whether `dir` holds a site written by `vyasa build`
+pub fn is_site(dir: &Path) -> bool {
+    dir.join(SITE_MARKER).is_file()
+}
+
 // _| vyasa check checks all non human meant files |_
 // human-meant: configs, data files, binaries - skip these
 // source code and docs: scan for mantras
@@ -794,7 +1341,15 @@ fn should_skip_file(ext: &str) -> bool {
     )
 }
 
-pub fn find_repo_root(path: &Path) -> Option<std::path::PathBuf> {
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Nearest folder at or above `path` holding `.vyasa` or `.git`
+pub fn find_repo_root(path: &Path) -> Option<PathBuf> {
     let mut current = if path.is_file() {
         path.parent().map(|p| p.to_path_buf())
     } else {
@@ -811,39 +1366,237 @@ pub fn find_repo_root(path: &Path) -> Option<std::path::PathBuf> {
     None
 }
 
-fn load_config(repo_root: &Path) -> Config {
-    let config_file = repo_root.join(".vyasa/config.json");
-    if let Ok(content) = fs::read_to_string(&config_file) {
-        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&content) {
-            return Config {
-                name: json.get("name").and_then(|v| v.as_str()).map(|s| s.to_string()),
-            };
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::{anusrit_marker, mula_marker};
+    use crate::testing::TempShastra;
+
+    /// `content` parsed as a file of the type `file` names, with the default config
+    fn parse(content: &str, file: &str) -> ParsedFile {
+        parse_file(content, file, &Config::default().comment_syntax(Path::new(file)))
+    }
+
+    #[test]
+    fn mantras_are_normalized() {
+        assert_eq!(normalize_mantra("cafe\u{301}"), "caf\u{e9}");
+        assert_eq!(normalize_mantra("zero\u{200b}width"), "zerowidth");
+        assert_eq!(normalize_mantra("  many \u{a0}kinds\n\tof   space "), "many kinds of space");
+        assert_eq!(normalize_mantra("already canonical"), "already canonical");
+    }
+
+    #[test]
+    fn spelling_issues_name_what_is_not_canonical() {
+        assert!(spelling_issues("canonical text").is_empty());
+        assert_eq!(spelling_issues("cafe\u{301}"), vec!["not unicode NFC"]);
+        assert_eq!(spelling_issues("a\u{feff}b"), vec!["zero-width character"]);
+        assert_eq!(spelling_issues("a\u{a0}b"), vec!["whitespace other than plain space"]);
+        assert_eq!(spelling_issues("a  b"), vec!["repeated spaces"]);
+    }
+
+    #[test]
+    fn mantras_are_defined_and_used_by_their_canonical_text() {
+        let content = format!(
+            "> {} - spaced out.\n\nsee {}\n",
+            mula_marker("two  spaces"),
+            anusrit_marker("two\u{a0}spaces")
+        );
+        let file = parse(&content, "notes.md");
+        assert_eq!(file.bhasyas[0].mulas[0].mantra_text, "two spaces");
+        assert_eq!(file.anusrits[0].mantra_text, "two spaces");
+        let raw: Vec<&str> = file.non_canonical.iter().map(|s| s.raw.as_str()).collect();
+        assert_eq!(raw, vec!["two  spaces", "two\u{a0}spaces"]);
+    }
+
+    #[test]
+    fn markers_that_are_not_mulas_are_not_spelling_issues() {
+        let content = format!(
+            "some prose with {} inside.\n\n> text before {} - mid-line.\n",
+            mula_marker("not  a mantra"),
+            mula_marker("mid  line")
+        );
+        let file = parse(&content, "notes.md");
+        assert!(file.bhasyas.iter().all(|bhasya| bhasya.mulas.is_empty()));
+        assert!(file.non_canonical.is_empty());
     }
-    Config::default()
-}
 
-fn load_shastra_config(repo_root: &Path) -> ShastraConfig {
-    let mut config = ShastraConfig::default();
+    #[test]
+    fn mantras_and_anusrits_wrap_across_lines() {
+        let mula = mula_marker("a mantra long enough to wrap");
+        let (first, second) = mula.split_at(mula.find(" to ").unwrap());
+        let anusrit = anusrit_marker("a mantra long enough to wrap");
+        let (third, fourth) = anusrit.split_at(anusrit.find(" enough").unwrap());
+        let content = format!("// > {}\n// >{} - text.\nfn f() {{}}\n// see {}\n//{}\n", first, second, third, fourth);
+        let file = parse(&content, "code.rs");
+        let mula = &file.bhasyas[0].mulas[0];
+        assert_eq!(mula.mantra_text, "a mantra long enough to wrap");
+        assert_eq!((mula.span.start.line, mula.span.end.line), (1, 2));
+        assert_eq!(file.anusrits[0].mantra_text, "a mantra long enough to wrap");
+        assert_eq!((file.anusrits[0].span.start.line, file.anusrits[0].span.end.line), (4, 5));
+        // a soft line break is not a spelling issue
+        assert!(file.non_canonical.is_empty());
+    }
+
+    #[test]
+    fn spans_count_columns_in_characters_and_offsets_in_bytes() {
+        let first = format!("> {} - \u{e9}.", mula_marker("m"));
+        let content = format!("{}\n\n\u{fc} \u{e9} {}\n", first, anusrit_marker("m"));
+        let file = parse(&content, "notes.md");
+        let bhasya = &file.bhasyas[0];
+        assert_eq!(bhasya.span.bytes(), 0..first.len());
+        let mula = &bhasya.mulas[0].span;
+        assert_eq!((mula.start.line, mula.start.column, mula.start.byte), (1, 3, 2));
+        assert_eq!(&content[mula.bytes()], mula_marker("m"));
+        let anusrit = &file.anusrits[0].span;
+        let byte = first.len() + 2 + "\u{fc} \u{e9} ".len();
+        assert_eq!((anusrit.start.line, anusrit.start.column, anusrit.start.byte), (3, 5, byte));
+        assert_eq!(&content[anusrit.bytes()], anusrit_marker("m"));
+    }
+
+    #[test]
+    fn bhasyas_live_in_block_comments() {
+        let content = format!(
+            "/*\n * > {} - one\n * > two\n */\nlet x = 1; /* > {} - not at a line start */\n",
+            mula_marker("block"),
+            mula_marker("inline")
+        );
+        let file = parse(&content, "code.c");
+        assert_eq!(file.bhasyas.len(), 1);
+        assert_eq!(file.bhasyas[0].mulas[0].mantra_text, "block");
+        assert_eq!(file.bhasyas[0].paragraph, format!("{} - one\ntwo", mula_marker("block")));
+
+        // the closing delimiter is not part of the bhasya
+        let content = format!("(* > {} - closed here *)\n", mula_marker("ocaml"));
+        let file = parse(&content, "code.ml");
+        assert_eq!(file.bhasyas[0].paragraph, format!("{} - closed here", mula_marker("ocaml")));
+    }
+
+    #[test]
+    fn python_strings_opened_mid_line_are_not_docstrings() {
+        // the quote closing the string would open a docstring swallowing the comment
+        let content =
+            format!("x = \"\"\"\nin a string\n\"\"\"\ny = 1\n\n# > {} - a comment.\n", mula_marker("comment"));
+        let file = parse(&content, "code.py");
+        assert_eq!(file.bhasyas.len(), 1);
+        assert_eq!(file.bhasyas[0].mulas[0].mantra_text, "comment");
+
+        // a string opened and closed on one line changes nothing
+        let content = format!("y = \"\"\"one line\"\"\"\n\"\"\"\n> {} - a docstring.\n\"\"\"\n", mula_marker("doc"));
+        assert_eq!(parse(&content, "code.py").bhasyas.len(), 1);
+    }
+
+    #[test]
+    fn comment_syntax_follows_the_file_extension() {
+        let bhasya = |marker: &str| format!("{} > {} - text.\n", marker, mula_marker("m"));
+        assert_eq!(parse(&bhasya("#"), "script.py").bhasyas.len(), 1);
+        assert_eq!(parse(&bhasya("--"), "query.sql").bhasyas.len(), 1);
+        assert_eq!(parse(&bhasya("#"), "Makefile").bhasyas.len(), 1);
+        // `//` is no comment in python, nor `#` in c
+        assert!(parse(&bhasya("//"), "script.py").bhasyas.is_empty());
+        assert!(parse(&bhasya("#"), "code.c").bhasyas.is_empty());
+        // files of unknown type take every known syntax
+        assert_eq!(parse(&bhasya(";"), "notes.unknown").bhasyas.len(), 1);
+
+        let mut config = Config::default();
+        config.comments.insert("c".to_string(), CommentSyntax { line: vec!["#".to_string()], ..Default::default() });
+        let syntax = config.comment_syntax(Path::new("code.c"));
+        assert_eq!(parse_file(&bhasya("#"), "code.c", &syntax).bhasyas.len(), 1);
+    }
 
-    // load .vyasa/shastra.json - main shastra aliases
-    let shastra_file = repo_root.join(".vyasa/shastra.json");
-    if let Ok(content) = fs::read_to_string(&shastra_file) {
-        if let Ok(aliases) = serde_json::from_str::<HashMap<String, String>>(&content) {
-            config.aliases = aliases;
+    #[test]
+    fn rust_doc_comments_hold_bhasyas() {
+        for prefix in ["///", "//!", "//"] {
+            let content = format!("{} > {} - text.\nfn f() {{}}\n", prefix, mula_marker("m"));
+            let file = parse(&content, "lib.rs");
+            // no stray `/` or `!` before the quote
+            assert_eq!(file.bhasyas[0].paragraph, format!("{} - text.", mula_marker("m")), "{}", prefix);
         }
+        let content = format!("#[doc = \"> {} - text.\"]\n#[doc = \"> more\"]\n", mula_marker("m"));
+        let file = parse(&content, "lib.rs");
+        assert_eq!(file.bhasyas[0].paragraph, format!("{} - text.\nmore", mula_marker("m")));
+        assert!(parse(&content, "lib.c").bhasyas.is_empty());
     }
 
-    // load .vyasa/shastra.local.json - local overrides (gitignored)
-    let local_file = repo_root.join(".vyasa/shastra.local.json");
-    if let Ok(content) = fs::read_to_string(&local_file) {
-        if let Ok(local_aliases) = serde_json::from_str::<HashMap<String, String>>(&content) {
-            // local overrides main config
-            for (alias, path) in local_aliases {
-                config.aliases.insert(alias, path);
-            }
+    #[test]
+    fn doc_attribute_strings_are_read_with_their_escapes_resolved() {
+        let markdown = parse(&format!("> {} - text.\n", mula_marker("say \"hi\" \\ é")), "notes.md");
+        let mantra = &markdown.bhasyas[0].mulas[0].mantra_text;
+        for line in [
+            format!("#[doc = \"> {} - text.\"]\n", mula_marker("say \\\"hi\\\" \\\\ \\u{e9}")),
+            format!("#[doc = r#\"> {} - text.\"#]\n", mula_marker("say \"hi\" \\ é")),
+        ] {
+            let file = parse(&line, "lib.rs");
+            assert_eq!(&file.bhasyas[0].mulas[0].mantra_text, mantra, "{}", line);
         }
+
+        // spans after an escape are where the text is in the source
+        let line = format!("#[doc = \"\\\"q\\\" {}\"]\n", anusrit_marker("m"));
+        let file = parse(&line, "lib.rs");
+        let at = line.find(&anusrit_marker("m")).unwrap();
+        assert_eq!(file.anusrits[0].span.start.byte, at);
+        assert_eq!(file.anusrits[0].span.start.column, at + 1);
+        assert_eq!(file.anusrits[0].span.end.byte, at + anusrit_marker("m").len());
     }
 
-    config
+    #[test]
+    fn tyakta_lines_record_date_replacement_and_reason() {
+        let note = |line: &str| {
+            let file = parse(&format!("{}\n> {} - abandoned.\n", line, mula_marker("old")), "notes.md");
+            match &file.bhasyas[0].kind {
+                BhasyaKind::Tyakta(note) => note.clone(),
+                kind => panic!("{:?}", kind),
+            }
+        };
+        let replacement = anusrit_marker("new");
+
+        let full = note(&format!("tyakta: 2024-05-01 -> {}@other - superseded", replacement));
+        assert_eq!(full.date, NaiveDate::from_ymd_opt(2024, 5, 1));
+        let anusrit = full.replacement.unwrap();
+        assert_eq!((anusrit.mantra_text.as_str(), anusrit.shastra.as_deref()), ("new", Some("other")));
+        assert_eq!(full.reason.as_deref(), Some("superseded"));
+
+        assert_eq!(note("tyakta:"), TyaktaNote::default());
+        assert_eq!(note("tyakta: no longer true").reason.as_deref(), Some("no longer true"));
+        // a malformed date is kept as the reason, and doesn't hide the replacement
+        let malformed = note(&format!("tyakta: 2024-13-01 -> {}", replacement));
+        assert_eq!((malformed.date, malformed.reason.as_deref()), (None, Some("2024-13-01")));
+        assert_eq!(malformed.replacement.unwrap().mantra_text, "new");
+    }
+
+    #[test]
+    fn built_site_is_not_scanned() {
+        let bhasya = format!("> {} - a shastra of one mantra.\n", mula_marker("one"));
+        let shastra = TempShastra::new("built", &[("notes.md", &bhasya), ("site/notes.html", &bhasya)]);
+        assert_eq!(shastra.parse().bhasyas.len(), 2);
+        shastra.write(&format!("site/{}", SITE_MARKER), "");
+        let repo = shastra.parse();
+        assert_eq!(repo.bhasyas.len(), 1);
+        assert!(!repo.bhasyas[0].file.contains("site"));
+    }
+
+    #[test]
+    fn scans_follows_the_ignore_files_of_the_walk() {
+        let shastra = TempShastra::new(
+            "ignored",
+            &[
+                (".gitignore", "*.log\nbuild/\n"),
+                (".git/info/exclude", "scratch.rs\n"),
+                ("src/.gitignore", "generated.rs\n!keep.log\n"),
+                ("src/lib.rs", ""),
+                ("src/generated.rs", ""),
+                ("src/keep.log", ""),
+                ("site/.vyasa-site", ""),
+            ],
+        );
+        let repo = shastra.parse();
+        let scans = |relative: &str| repo.scans(&shastra.path(relative));
+        assert!(scans("src/lib.rs"));
+        assert!(scans("src/keep.log"));
+        assert!(!scans("src/generated.rs"));
+        assert!(!scans("debug.log"));
+        assert!(!scans("build/out.rs"));
+        assert!(!scans("scratch.rs"));
+        assert!(!scans(".hidden/lib.rs"));
+        assert!(!scans("site/notes.md"));
+    }
 }
@@ -0,0 +1,166 @@
+use crate::edit::{Changes, QuoteAffixes, TextEdit};
+use crate::parser::{normalize_mantra, Bhasya, BhasyaKind, Repository};
+use crate::shastra::ShastraResolver;
+use std::fs;
+use std::path::Path;
+
+/// A bhasya quoted from an observed shastra, and the edit placing it in a file
+#[derive(Debug, Default)]
+pub struct QuotePlan {
+    pub shastra: String,
+    pub mantra: String,
+    /// Where the bhasya lives in the observed shastra - `file:line`, from its root
+    pub source: String,
+    /// The block, `shastra:` line first, without comment markers
+    pub lines: Vec<String>,
+    /// Appending the block to the target file, if one was given
+    pub changes: Changes,
+}
+
+// _| vyasa quote copies a bhasya verbatim |_
+/// Plan quoting the mula bhasya of `mantra` from observed shastra `alias`,
+/// appended to `into` in its comment style. Tyakta bhasyas can't be quoted.
+pub fn plan(repo: &Repository, alias: &str, mantra: &str, into: Option<&Path>) -> Result<QuotePlan, String> {
+    let mantra = normalize_mantra(mantra);
+    let already = repo.bhasyas.iter().find(|b| {
+        matches!(&b.kind, BhasyaKind::Uddhrit(s) if s == alias) && b.mulas.iter().any(|m| m.mantra_text == mantra)
+    });
+    if let Some(bhasya) = already {
+        return Err(format!("^{}^ is already quoted from '{}' at {}:{}", mantra, alias, bhasya.file, bhasya.line));
+    }
+
+    let mut resolver = ShastraResolver::new(repo);
+    let external = resolver.resolve(alias).map_err(|e| e.to_string())?;
+    let bhasya = source_bhasya(external, alias, &mantra, false)?;
+
+    let mut lines = vec![format!("shastra: {}", alias)];
+    lines.extend(quote_lines(bhasya));
+    let mut plan = QuotePlan {
+        shastra: alias.to_string(),
+        mantra,
+        source: format!("{}:{}", relative_to(external, &bhasya.file), bhasya.line),
+        lines,
+        changes: Changes::default(),
+    };
+    if let Some(into) = into {
+        plan.changes.push(append_block(repo, into, &plan.lines)?);
+    }
+    Ok(plan)
+}
+
+/// The bhasya defining `mantra` in `external` (known to us as `alias`) - the
+/// first if there are several. Tyakta bhasyas are only found with `allow_tyakta`.
+pub fn source_bhasya<'a>(
+    external: &'a Repository,
+    alias: &str,
+    mantra: &str,
+    allow_tyakta: bool,
+) -> Result<&'a Bhasya, String> {
+    let defining = |bhasya: &&Bhasya| {
+        let kind_ok = match bhasya.kind {
+            BhasyaKind::Mula => true,
+            BhasyaKind::Tyakta(_) => allow_tyakta,
+            _ => false,
+        };
+        kind_ok && bhasya.mulas.iter().any(|m| m.mantra_text == mantra)
+    };
+    if let Some(bhasya) = external.bhasyas_for_mantra(mantra).into_iter().find(defining) {
+        return Ok(bhasya);
+    }
+    // _| quoting tyakta is an error |_
+    if external.is_tyakta(mantra) {
+        return Err(match external.replacement_for(mantra) {
+            Some(replacement) => format!(
+                "^{}^ is tyakta in '{}' - quote its replacement ^{}^ instead",
+                mantra, alias, replacement.mantra_text
+            ),
+            None => format!("^{}^ is tyakta in '{}' - abandoned bhasyas can't be quoted", mantra, alias),
+        });
+    }
+    Err(format!("no mula bhasya for ^{}^ in shastra '{}'", mantra, alias))
+}
+
+/// A bhasya's text as `>` quote lines
+pub fn quote_lines(bhasya: &Bhasya) -> Vec<String> {
+    bhasya
+        .paragraph
+        .lines()
+        .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) })
+        .collect()
+}
+
+/// An edit appending `lines` to the end of `file`, separated from what is
+/// there by a blank line, in the file's comment style
+pub fn append_block(repo: &Repository, file: &Path, lines: &[String]) -> Result<TextEdit, String> {
+    let syntax = repo.config.comment_syntax(file);
+    let affixes =
+        QuoteAffixes::for_syntax(&syntax).map_err(|e| format!("can't add a bhasya to {}: {}", file.display(), e))?;
+    let content = fs::read_to_string(file).unwrap_or_default();
+    let separator = match content.as_str() {
+        "" => "",
+        c if c.ends_with('\n') => "\n",
+        _ => "\n\n",
+    };
+    let text = format!("{}{}\n", separator, affixes.lines(lines));
+    Ok(TextEdit::insert(&file.to_string_lossy(), content.len(), text))
+}
+
+/// A file of `shastra` relative to its root
+pub(crate) fn relative_to(shastra: &Repository, file: &str) -> String {
+    shastra
+        .root
+        .as_deref()
+        .and_then(|root| Path::new(file).strip_prefix(root).ok())
+        .map_or_else(|| file.to_string(), |p| p.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::{anusrit_marker, mula_marker};
+    use crate::testing::TempShastra;
+
+    /// A shastra observing `physics`, which defines `energy` and has abandoned `ether`
+    fn observing() -> (TempShastra, TempShastra) {
+        let physics = format!(
+            "> {} - in an isolated system\n> it stays constant.\n\ntyakta: -> {}\n> {} - fills space.\n",
+            mula_marker("energy"),
+            anusrit_marker("energy"),
+            mula_marker("ether")
+        );
+        let physics = TempShastra::new("physics", &[("notes.md", &physics)]);
+        let ours = TempShastra::new("ours", &[("code.rs", "fn f() {}\n")]);
+        ours.observe("physics", &physics);
+        (ours, physics)
+    }
+
+    #[test]
+    fn quotes_a_bhasya_verbatim_in_the_comment_style_of_the_file() {
+        let (ours, _physics) = observing();
+        let quote = plan(&ours.parse(), "physics", "energy", Some(&ours.path("code.rs"))).unwrap();
+        assert_eq!(quote.source, "notes.md:1");
+        quote.changes.write().unwrap();
+        let expected = format!(
+            "fn f() {{}}\n\n// shastra: physics\n// > {} - in an isolated system\n// > it stays constant.\n",
+            mula_marker("energy")
+        );
+        assert_eq!(ours.read("code.rs"), expected);
+
+        let repo = ours.parse();
+        assert!(matches!(&repo.bhasyas[0].kind, BhasyaKind::Uddhrit(s) if s == "physics"));
+        let error = plan(&repo, "physics", "energy", None).unwrap_err();
+        assert!(error.contains("already quoted"), "{}", error);
+    }
+
+    #[test]
+    fn tyakta_and_unknown_bhasyas_are_not_quoted() {
+        let (ours, _physics) = observing();
+        let repo = ours.parse();
+        let error = plan(&repo, "physics", "ether", None).unwrap_err();
+        assert!(error.contains("quote its replacement ^energy^"), "{}", error);
+        assert!(plan(&repo, "physics", "mass", None).unwrap_err().contains("no mula bhasya"));
+        assert!(plan(&repo, "chemistry", "energy", None).unwrap_err().contains("undefined shastra"));
+        let error = plan(&repo, "physics", "energy", Some(&ours.path("style.css"))).unwrap_err();
+        assert!(error.contains("no line comments"), "{}", error);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::edit::{anusrit_marker, mula_marker, Changes, QuoteAffixes, TextEdit};
+use crate::parser::{normalize_mantra, BhasyaKind, Repository};
+use crate::shastra::{uses_in_observed, ExternalUse};
+use crate::tyakta::tyakta_line;
+use chrono::Local;
+use std::fs;
+use std::path::Path;
+
+/// The edits that reword a mantra, and the uses vyasa can't edit
+#[derive(Debug, Default)]
+pub struct RenamePlan {
+    pub old: String,
+    pub new: String,
+    pub changes: Changes,
+    /// Mula definitions rewritten
+    pub mulas: usize,
+    /// Anusrits rewritten
+    pub anusrits: usize,
+    /// Uses in observed shastras, which will break
+    pub external: Vec<ExternalUse>,
+}
+
+/// Plan rewording mantra `old` to `new` everywhere in `repo`: its mula
+/// definitions (in mula bhasyas and in `shastra: <self>` quotes) and every
+/// anusrit to it. With `tombstone`, a `tyakta:` bhasya for the old text,
+/// dated today and naming the new one as its replacement, is added after
+/// its first bhasya.
+pub fn plan(repo: &Repository, old: &str, new: &str, tombstone: bool) -> Result<RenamePlan, String> {
+    let old = normalize_mantra(old);
+    let new = normalize_mantra(new);
+    if new.is_empty() {
+        return Err("the new mantra text is empty".to_string());
+    }
+    if ["^**", "**^", "_|", "|_"].iter().any(|m| new.contains(m)) {
+        return Err("the new mantra text can't contain mantra or anusrit markers".to_string());
+    }
+    if old == new {
+        return Err("the old and new mantra text are the same".to_string());
+    }
+    if !repo.is_live(&old) {
+        return Err(format!("no live mantra ^{}^ in this shastra", old));
+    }
+    if repo.mantras.get(&new).is_some_and(|info| !info.mula_bhasyas.is_empty()) {
+        return Err(format!("^{}^ is already a mantra - rename to unused text", new));
+    }
+
+    let self_name = repo.config.name.as_deref();
+    let mut plan = RenamePlan { old: old.clone(), new: new.clone(), ..RenamePlan::default() };
+
+    for bhasya in &repo.bhasyas {
+        let defines_here = match &bhasya.kind {
+            BhasyaKind::Mula => true,
+            BhasyaKind::Uddhrit(shastra) => Some(shastra.as_str()) == self_name,
+            _ => false,
+        };
+        if defines_here {
+            for mula in bhasya.mulas.iter().filter(|m| m.mantra_text == old) {
+                plan.changes.push(TextEdit::replace(&bhasya.file, mula.span.bytes(), mula_marker(&new)));
+                plan.mulas += 1;
+            }
+        }
+    }
+    for anusrit in repo.anusrits_to(&old) {
+        plan.changes.push(TextEdit::replace(&anusrit.file, anusrit.span.bytes(), anusrit_marker(&new)));
+        plan.anusrits += 1;
+    }
+
+    if tombstone {
+        let first = repo
+            .bhasyas_for_mantra(&old)
+            .into_iter()
+            .find(|b| matches!(b.kind, BhasyaKind::Mula) && b.mulas.iter().any(|m| m.mantra_text == old))
+            .ok_or_else(|| format!("no mula bhasya for ^{}^", old))?;
+        let content = fs::read_to_string(&first.file).map_err(|e| format!("failed to read {}: {}", first.file, e))?;
+        let syntax = repo.config.comment_syntax(Path::new(&first.file));
+        let affixes = QuoteAffixes::after_line(&content, first.span.end.byte, &syntax)
+            .map_err(|e| format!("can't leave a tyakta bhasya after {}:{}: {}", first.file, first.line, e))?;
+        let lines = [
+            String::new(),
+            tyakta_line(Some(Local::now().date_naive()), Some(&new)),
+            format!("> {} - reworded.", mula_marker(&old)),
+        ];
+        plan.changes.push(TextEdit::insert(&first.file, first.span.end.byte, format!("\n{}", affixes.lines(&lines))));
+    }
+
+    plan.external = uses_in_observed(repo, &old);
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempShastra;
+
+    #[test]
+    fn rewords_definitions_and_anusrits() {
+        let notes = format!(
+            "> {} - defined.\n\nshastra: words\n> {} - quoted from ourselves.\n\nshastra: other\n> {} - theirs.\n",
+            mula_marker("old"),
+            mula_marker("old"),
+            mula_marker("old")
+        );
+        let code = format!("// uses {} and {}\n", anusrit_marker("old"), anusrit_marker("old  "));
+        let shastra = TempShastra::new("words", &[("notes.md", &notes), ("code.rs", &code)]);
+        let plan = plan(&shastra.parse(), "old", "new", false).unwrap();
+        assert_eq!((plan.mulas, plan.anusrits), (2, 2));
+        plan.changes.write().unwrap();
+
+        let notes = notes.replacen(&mula_marker("old"), &mula_marker("new"), 2);
+        assert_eq!(shastra.read("notes.md"), notes);
+        let code = format!("// uses {} and {}\n", anusrit_marker("new"), anusrit_marker("new"));
+        assert_eq!(shastra.read("code.rs"), code);
+    }
+
+    #[test]
+    fn refuses_text_that_is_taken_or_marked() {
+        let notes = format!("> {} - one.\n\n> {} - two.\n", mula_marker("one"), mula_marker("two"));
+        let shastra = TempShastra::new("words", &[("notes.md", &notes)]);
+        let repo = shastra.parse();
+        assert!(plan(&repo, "one", "two", false).unwrap_err().contains("already a mantra"));
+        assert!(plan(&repo, "one", &mula_marker("x"), false).unwrap_err().contains("markers"));
+        assert!(plan(&repo, "one", " one ", false).unwrap_err().contains("the same"));
+        assert!(plan(&repo, "three", "four", false).unwrap_err().contains("no live mantra"));
+    }
+
+    #[test]
+    fn lists_uses_in_observed_shastras() {
+        let ours = TempShastra::new("ours", &[("notes.md", &format!("> {} - ours.\n", mula_marker("old")))]);
+        let code = format!("// uses {}@ours\n", anusrit_marker("old"));
+        let theirs = TempShastra::new("theirs", &[("code.rs", &code)]);
+        ours.observe("theirs", &theirs);
+        let plan = plan(&ours.parse(), "old", "new", false).unwrap();
+        assert_eq!(plan.external.len(), 1);
+        assert_eq!(plan.external[0].shastra, "theirs");
+        assert_eq!(plan.external[0].kind, "anusrit");
+    }
+
+    #[test]
+    fn tombstone_continues_an_open_block_comment() {
+        let code = format!("/* > {} - alone\n */\n", mula_marker("old"));
+        let shastra = TempShastra::new("blocks", &[("code.rs", &code)]);
+        plan(&shastra.parse(), "old", "new", true).unwrap().changes.write().unwrap();
+
+        let line = tyakta_line(Some(Local::now().date_naive()), Some("new"));
+        let expected = format!(
+            "/* > {} - alone\n *\n * {}\n * > {} - reworded.\n */\n",
+            mula_marker("new"),
+            line,
+            mula_marker("old")
+        );
+        assert_eq!(shastra.read("code.rs"), expected);
+        let repo = shastra.parse();
+        assert!(matches!(repo.bhasyas[1].kind, BhasyaKind::Tyakta(_)));
+    }
+
+    #[test]
+    fn tombstone_is_refused_after_a_closed_block_comment() {
+        let code = format!("/* > {} - alone */\n", mula_marker("old"));
+        let shastra = TempShastra::new("blocks", &[("code.rs", &code)]);
+        let error = plan(&shastra.parse(), "old", "new", true).unwrap_err();
+        assert!(error.contains("closes with `*/`"), "{}", error);
+    }
+}
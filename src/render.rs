@@ -0,0 +1,697 @@
+use crate::parser::{parse_file, Anusrit, Bhasya, BhasyaKind, Repository};
+use crate::quote::relative_to;
+use crate::shastra::ShastraResolver;
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag, TagEnd};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+/// A shastra rendered as linked HTML pages, addressed by path from the site
+/// root: `index.html`, `files/<file>.html`, `mantras/<slug>.html`, and
+/// `s/<alias>/files/<file>.html` for the files of an observed shastra
+pub struct Site<'a> {
+    repo: &'a Repository,
+    resolver: &'a mut ShastraResolver,
+    /// Added to the end of every page - `vyasa serve` reloads pages with it
+    pub script: String,
+}
+
+/// Where an anusrit leads - the page holding its mula bhasya
+struct Link {
+    /// Alias of the observed shastra, None for ours
+    shastra: Option<String>,
+    /// The file, relative to the root of its shastra
+    file: String,
+}
+
+impl<'a> Site<'a> {
+    /// The site of `repo`, resolving observed shastras with `resolver` - a
+    /// long-running caller keeps one, so each is parsed once
+    pub fn new(repo: &'a Repository, resolver: &'a mut ShastraResolver) -> Self {
+        Site { repo, resolver, script: String::new() }
+    }
+
+    /// The page at `path`, None if there is no such page
+    pub fn render(&mut self, path: &str) -> Option<String> {
+        let base = "../".repeat(path.matches('/').count());
+        if path == "index.html" {
+            let body = self.index();
+            return Some(self.page(self.name(), &base, &body));
+        }
+        if let Some(relative) = path.strip_prefix("files/").and_then(|p| p.strip_suffix(".html")) {
+            let file = file_named(self.repo, relative)?;
+            let body = Renderer::new(self.repo, Some(&mut *self.resolver), "", &base).file(&file)?;
+            return Some(self.page(relative, &base, &body));
+        }
+        if let Some(slug) = path.strip_prefix("mantras/").and_then(|p| p.strip_suffix(".html")) {
+            let (mantra, body) = self.mantra(slug, &base)?;
+            return Some(self.page(&mantra, &base, &body));
+        }
+        let (alias, rest) = path.strip_prefix("s/")?.split_once('/')?;
+        let relative = rest.strip_prefix("files/")?.strip_suffix(".html")?;
+        let external = self.resolver.resolve(alias).ok()?;
+        let file = file_named(external, relative)?;
+        // links stay inside the observed shastra - its own observed shastras aren't ours to resolve
+        let body = Renderer::new(external, None, &format!("s/{}/", alias), &base).file(&file)?;
+        Some(self.page(&format!("{} in '{}'", relative, alias), &base, &body))
+    }
+
+    /// Every page: the index, one per file and one per mantra with a mula
+    /// bhasya, then the files of each observed shastra
+    pub fn paths(&mut self) -> Vec<String> {
+        let mut paths = vec!["index.html".to_string()];
+        paths.extend(files(self.repo).iter().map(|f| file_page(self.repo, f)));
+        let mut mantras: Vec<&String> =
+            self.repo.mantras.iter().filter(|(_, info)| !info.mula_bhasyas.is_empty()).map(|(m, _)| m).collect();
+        mantras.sort();
+        paths.extend(mantras.into_iter().map(|m| mantra_page(m)));
+        for alias in self.resolver.aliases() {
+            if let Ok(external) = self.resolver.resolve(&alias) {
+                paths.extend(files(external).iter().map(|f| format!("s/{}/{}", alias, file_page(external, f))));
+            }
+        }
+        paths
+    }
+
+    fn name(&self) -> &str {
+        self.repo.config.name.as_deref().unwrap_or("shastra")
+    }
+
+    // _| vyasa serve has a mantra index |_
+    /// Every live mantra with where it is explained and how often it is used,
+    /// then every file - both filtered as you type in the search box
+    fn index(&self) -> String {
+        let mut mantras: Vec<&String> = self.repo.mantras.keys().filter(|m| self.repo.is_live(m)).collect();
+        mantras.sort();
+        let mut body = format!("<h1>{}</h1>\n{}\n", escape(self.name()), SEARCH);
+        body.push_str("<h2>mantras</h2>\n<ul class=\"mantras\">\n");
+        for mantra in mantras {
+            let Some(bhasya) = defining_bhasya(self.repo, mantra) else {
+                continue;
+            };
+            body.push_str(&format!(
+                "<li><a href=\"{}\">{}</a> <span class=\"where\">{}:{}</span> <span class=\"uses\">{}</span></li>\n",
+                mantra_page(mantra),
+                escape(mantra),
+                escape(&relative_to(self.repo, &bhasya.file)),
+                bhasya.line,
+                count(self.repo.anusrits_to(mantra).len(), "anusrit")
+            ));
+        }
+        body.push_str("</ul>\n<h2>files</h2>\n<ul class=\"files\">\n");
+        for file in files(self.repo) {
+            let relative = relative_to(self.repo, &file);
+            body.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n", file_page(self.repo, &file), escape(&relative)));
+        }
+        body.push_str("</ul>\n");
+        body
+    }
+
+    // _| vyasa build has a page per mantra |_
+    /// The mantra named by `slug` and its page: its mula bhasyas, then where
+    /// it is used
+    fn mantra(&mut self, slug: &str, base: &str) -> Option<(String, String)> {
+        let repo = self.repo;
+        let (mantra, info) = repo.mantras.iter().find(|(m, _)| self::slug(m) == slug)?;
+        let mut renderer = Renderer::new(repo, Some(&mut *self.resolver), "", base);
+        let mut body = format!("<h1>{}</h1>\n", escape(mantra));
+        if !repo.is_live(mantra) {
+            body.push_str("<p><span class=\"badge tyakta\">tyakta</span></p>\n");
+        }
+
+        body.push_str(&format!("<h2>{}</h2>\n", count(info.mula_bhasyas.len(), "mula bhasya")));
+        for bhasya in info.mula_bhasyas.iter().filter_map(|&i| repo.bhasyas.get(i)) {
+            body.push_str(&format!(
+                "<p class=\"where\"><a href=\"{}{}#{}\">{}:{}</a></p>\n{}",
+                base,
+                file_page(repo, &bhasya.file),
+                slug,
+                escape(&relative_to(repo, &bhasya.file)),
+                bhasya.line,
+                renderer.bhasya(bhasya)
+            ));
+        }
+
+        let uses = repo.anusrits_to(mantra);
+        body.push_str(&format!("<h2>{}</h2>\n<ul class=\"uses\">\n", count(uses.len(), "anusrit")));
+        let mut contents = HashMap::new();
+        for anusrit in uses {
+            let content =
+                contents.entry(&anusrit.file).or_insert_with(|| fs::read_to_string(&anusrit.file).unwrap_or_default());
+            body.push_str(&format!(
+                "<li><a href=\"{}{}#a{}-{}\">{}:{}</a> <span class=\"context\">{}</span></li>\n",
+                base,
+                file_page(repo, &anusrit.file),
+                anusrit.span.start.line,
+                anusrit.span.start.column,
+                escape(&relative_to(repo, &anusrit.file)),
+                anusrit.line,
+                renderer.context(content, anusrit).unwrap_or_default()
+            ));
+        }
+        body.push_str("</ul>\n");
+        Some((mantra.clone(), body))
+    }
+
+    fn page(&self, title: &str, base: &str, body: &str) -> String {
+        format!(
+            "<!doctype html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title} - {name}</title>\n\
+             <style>{STYLE}</style>\n</head>\n<body>\n<nav><a href=\"{base}index.html\">{name}</a></nav>\n\
+             <main>\n{body}</main>\n{script}</body>\n</html>\n",
+            title = escape(title),
+            name = escape(self.name()),
+            script = self.script,
+        )
+    }
+}
+
+/// Files with bhasyas or anusrits, sorted
+fn files(repo: &Repository) -> BTreeSet<String> {
+    repo.bhasyas.iter().map(|b| &b.file).chain(repo.anusrits.iter().map(|a| &a.file)).cloned().collect()
+}
+
+/// The file of `repo` at `relative` from its root
+fn file_named(repo: &Repository, relative: &str) -> Option<String> {
+    files(repo).into_iter().find(|f| relative_to(repo, f) == relative)
+}
+
+/// Path of a file's page, from the site root
+fn file_page(repo: &Repository, file: &str) -> String {
+    format!("files/{}.html", relative_to(repo, file))
+}
+
+/// Path of a mantra's page, from the site root
+fn mantra_page(mantra: &str) -> String {
+    format!("mantras/{}.html", slug(mantra))
+}
+
+fn count(n: usize, noun: &str) -> String {
+    match n {
+        1 => format!("1 {}", noun),
+        n => format!("{} {}s", n, noun),
+    }
+}
+
+/// The bhasya an anusrit to `mantra` leads to: its first mula bhasya, or its
+/// first tyakta one if it has been abandoned
+fn defining_bhasya<'r>(repo: &'r Repository, mantra: &str) -> Option<&'r Bhasya> {
+    let bhasyas = repo.bhasyas_for_mantra(mantra);
+    let defines = |b: &&&Bhasya| b.mulas.iter().any(|m| m.mantra_text == mantra);
+    let first = bhasyas.iter().filter(defines).find(|b| b.kind == BhasyaKind::Mula);
+    first.or_else(|| bhasyas.iter().filter(defines).find(|b| matches!(b.kind, BhasyaKind::Tyakta(_)))).copied()
+}
+
+/// Where an anusrit of `repo` leads, as `vyasa check` resolves it - None if
+/// it is undefined, ambiguous, or points outside what `resolver` can reach
+fn resolve(repo: &Repository, resolver: Option<&mut ShastraResolver>, anusrit: &Anusrit) -> Option<Link> {
+    let mantra = &anusrit.mantra_text;
+    let local = || {
+        defining_bhasya(repo, mantra).map(|b| Link { shastra: None, file: relative_to(repo, &b.file) })
+    };
+    match anusrit.shastra.as_deref() {
+        Some(alias) if Some(alias) != repo.config.name.as_deref() => {
+            let external = resolver?.resolve(alias).ok()?;
+            let bhasya = defining_bhasya(external, mantra)?;
+            Some(Link { shastra: Some(alias.to_string()), file: relative_to(external, &bhasya.file) })
+        }
+        Some(_) => local(),
+        None if repo.has_any_bhasya_for_mantra(mantra) => local(),
+        None => {
+            let resolver = resolver?;
+            let mut links = Vec::new();
+            for alias in resolver.aliases() {
+                let Ok(external) = resolver.resolve(&alias) else {
+                    continue;
+                };
+                if let Some(bhasya) = defining_bhasya(external, mantra).filter(|_| external.is_live(mantra)) {
+                    let file = relative_to(external, &bhasya.file);
+                    links.push(Link { shastra: Some(alias), file });
+                }
+            }
+            // several is ambiguous - check says so, and we don't guess
+            (links.len() == 1).then(|| links.remove(0))
+        }
+    }
+}
+
+/// Renders text of one shastra into a page. `prefix` is where the
+/// shastra's pages live and `base` leads from the page back to the site root.
+struct Renderer<'r, 's> {
+    repo: &'r Repository,
+    /// Resolves anusrits into observed shastras - without it they aren't linked
+    resolver: Option<&'s mut ShastraResolver>,
+    prefix: String,
+    base: String,
+}
+
+impl<'r, 's> Renderer<'r, 's> {
+    fn new(repo: &'r Repository, resolver: Option<&'s mut ShastraResolver>, prefix: &str, base: &str) -> Self {
+        Renderer { repo, resolver, prefix: prefix.to_string(), base: base.to_string() }
+    }
+
+    // _| vyasa serve links anusrits to their mula bhasya |_
+    /// The body of a file's page: markdown rendered with its mula markers as
+    /// anchors, anusrits as links and each bhasya marked with its kind; other
+    /// files as source with the same links. None if the file is gone or
+    /// changed since it was parsed, so the spans no longer fit it
+    fn file(&mut self, file: &str) -> Option<String> {
+        let repo = self.repo;
+        let content = fs::read_to_string(file).ok()?;
+        let markdown = file.ends_with(".md") || file.ends_with(".markdown");
+        let bhasyas: Vec<&Bhasya> = repo.bhasyas.iter().filter(|b| b.file == file).collect();
+        let mut anusrits: Vec<&Anusrit> = repo
+            .anusrits
+            .iter()
+            .chain(bhasyas.iter().flat_map(|b| &b.anusrits))
+            .filter(|a| a.file == file)
+            .collect();
+        if !markdown {
+            // markdown shows them in the tyakta badge instead
+            anusrits.extend(repo.tyakta_replacements().filter(|a| a.file == file));
+        }
+        let mut edits = self.markers(&content, &bhasyas, anusrits, true);
+
+        if !markdown {
+            // each bhasya's comment lines, marked with its kind
+            for bhasya in &bhasyas {
+                let span = bhasya.span.bytes();
+                let class = kind_class(&bhasya.kind);
+                edits.push((span.start..span.start, format!("<span class=\"bhasya {}\">", class)));
+                edits.push((span.end..span.end, "</span>".to_string()));
+            }
+            if !fits(&content, &edits) {
+                return None;
+            }
+            edits.sort_by_key(|(range, _)| (range.start, range.end));
+            return Some(format!("<pre class=\"source\"><code>{}</code></pre>\n", splice(&content, &edits, escape)));
+        }
+
+        // the `shastra:` / `khandita:` / `tyakta:` line before a bhasya becomes its badge
+        for bhasya in &bhasyas {
+            if let (Some(line), Some(badge)) = (prefix_line(&content, bhasya), self.badge(&bhasya.kind)) {
+                edits.push((line, badge));
+            }
+        }
+        if !fits(&content, &edits) || bhasyas.iter().any(|b| content.get(b.span.bytes()).is_none()) {
+            return None;
+        }
+        edits.sort_by_key(|(range, _)| (range.start, range.end));
+        let text = splice(&content, &edits, |s| s.to_string());
+
+        // where each bhasya's quote block starts in the spliced text, by line
+        let lines = LineIndex::new(&text);
+        let mut blocks = HashMap::new();
+        for bhasya in &bhasyas {
+            let start = bhasya.span.start.byte;
+            let shift: isize = edits
+                .iter()
+                .filter(|(range, _)| range.end <= start)
+                .map(|(range, html)| html.len() as isize - range.len() as isize)
+                .sum();
+            let line = lines.line_of(start.saturating_add_signed(shift));
+            let panel = match bhasya.kind {
+                BhasyaKind::Mula if self.prefix.is_empty() => backlinks(repo, bhasya, &self.base),
+                _ => String::new(),
+            };
+            blocks.insert(line, (kind_class(&bhasya.kind), panel));
+        }
+        Some(markdown_html(&text, blocks))
+    }
+
+    /// A bhasya on its own, as a mantra page shows it: its badge, then its
+    /// text as a quote with the same links as on its file's page
+    fn bhasya(&mut self, bhasya: &Bhasya) -> String {
+        let quote: String = bhasya
+            .paragraph
+            .lines()
+            .map(|line| if line.is_empty() { ">\n".to_string() } else { format!("> {}\n", line) })
+            .collect();
+        // parsed again as markdown, for where the markers are in the quote
+        let syntax = self.repo.config.comment_syntax(Path::new("bhasya.md"));
+        let parsed = parse_file(&quote, &bhasya.file, &syntax);
+        let bhasyas: Vec<&Bhasya> = parsed.bhasyas.iter().collect();
+        let anusrits = bhasyas.iter().flat_map(|b| &b.anusrits).chain(&parsed.anusrits).collect();
+        let mut edits = self.markers(&quote, &bhasyas, anusrits, false);
+        edits.sort_by_key(|(range, _)| (range.start, range.end));
+
+        let mut html = self.badge(&bhasya.kind).map(|badge| format!("<p>{}</p>\n", badge)).unwrap_or_default();
+        let blocks = HashMap::from([(0, (kind_class(&bhasya.kind), String::new()))]);
+        html.push_str(&markdown_html(&splice(&quote, &edits, |s| s.to_string()), blocks));
+        html
+    }
+
+    /// The line of an anusrit, without its comment or quote markers, with
+    /// the markers on it linked. None if the anusrit no longer fits `content`
+    fn context(&mut self, content: &str, anusrit: &Anusrit) -> Option<String> {
+        let repo = self.repo;
+        let start = content.get(..anusrit.span.start.byte)?.rfind('\n').map_or(0, |i| i + 1);
+        let end = content[start..].find('\n').map_or(content.len(), |i| start + i);
+        let bhasyas: Vec<&Bhasya> = repo.bhasyas.iter().filter(|b| b.file == anusrit.file).collect();
+        let anusrits = repo
+            .anusrits
+            .iter()
+            .chain(bhasyas.iter().flat_map(|b| &b.anusrits))
+            .filter(|a| a.file == anusrit.file)
+            .collect();
+        let mut edits: Vec<_> = self
+            .markers(content, &bhasyas, anusrits, false)
+            .into_iter()
+            .filter(|(range, _)| start <= range.start && range.end <= end)
+            .collect();
+        if !fits(content, &edits) {
+            return None;
+        }
+        edits.sort_by_key(|(range, _)| (range.start, range.end));
+        let first = edits.first().map_or(end, |(range, _)| range.start);
+        let before = &content[start..first];
+        let start = start + before.len() - before.trim_start_matches([' ', '\t', '>', '/', '#', '*', '-', ';']).len();
+        let edits: Vec<_> =
+            edits.into_iter().map(|(range, html)| (range.start - start..range.end - start, html)).collect();
+        Some(splice(content[start..end].trim_end(), &edits, escape))
+    }
+
+    /// Mula markers and anusrits as html, by their range in `content`, in no
+    /// particular order. Without `anchored` they carry no ids, for text shown
+    /// away from its file.
+    fn markers(
+        &mut self,
+        content: &str,
+        bhasyas: &[&Bhasya],
+        anusrits: Vec<&Anusrit>,
+        anchored: bool,
+    ) -> Vec<(Range<usize>, String)> {
+        let mut edits = Vec::new();
+        let mut anchors = BTreeSet::new();
+        for bhasya in bhasyas {
+            for mula in &bhasya.mulas {
+                let id = slug(&mula.mantra_text);
+                // a quote and a refutation of one mantra share a page - links go to the first
+                let anchor = match anchored && anchors.insert(id.clone()) {
+                    true => format!(" id=\"{}\"", id),
+                    false => String::new(),
+                };
+                // our mantras have their own pages, an observed shastra's only their anchor
+                let href = match self.prefix.is_empty() {
+                    true => format!("{}{}", self.base, mantra_page(&mula.mantra_text)),
+                    false => format!("#{}", id),
+                };
+                let html = format!("<a class=\"mula\"{} href=\"{}\">{}</a>", anchor, href, escape(&mula.mantra_text));
+                edits.push((mula.span.bytes(), html));
+            }
+        }
+        for anusrit in anusrits {
+            let href = self.href(anusrit);
+            let (range, text) = anusrit_text(content, anusrit);
+            let id = match anchored {
+                true => format!(" id=\"a{}-{}\"", anusrit.span.start.line, anusrit.span.start.column),
+                false => String::new(),
+            };
+            let html = match href {
+                Some(href) => format!("<a class=\"anusrit\"{} href=\"{}\">{}</a>", id, escape(&href), text),
+                None => {
+                    format!("<span class=\"anusrit undefined\"{} title=\"no single mula bhasya\">{}</span>", id, text)
+                }
+            };
+            edits.push((range, html));
+        }
+        edits
+    }
+
+    /// Link to the mula bhasya an anusrit leads to, None if it leads nowhere
+    fn href(&mut self, anusrit: &Anusrit) -> Option<String> {
+        let link = resolve(self.repo, self.resolver.as_deref_mut(), anusrit)?;
+        let prefix = link.shastra.map_or(self.prefix.clone(), |alias| format!("s/{}/", alias));
+        Some(format!("{}{}files/{}.html#{}", self.base, prefix, link.file, slug(&anusrit.mantra_text)))
+    }
+
+    /// What a `shastra:`, `khandita:` or `tyakta:` line says, as a badge
+    fn badge(&mut self, kind: &BhasyaKind) -> Option<String> {
+        let badge = match kind {
+            BhasyaKind::Mula => return None,
+            BhasyaKind::Uddhrit(shastra) => {
+                format!("<span class=\"badge uddhrit\">uddhrit · {}</span>", escape(shastra))
+            }
+            BhasyaKind::Khandita(shastra) => {
+                format!("<span class=\"badge khandita\">khandita · {}</span>", escape(shastra))
+            }
+            BhasyaKind::Tyakta(note) => {
+                let mut badge = "<span class=\"badge tyakta\">tyakta</span>".to_string();
+                if let Some(date) = note.date {
+                    badge.push_str(&format!(" {}", date));
+                }
+                if let Some(replacement) = &note.replacement {
+                    let mantra = escape(&replacement.mantra_text);
+                    match self.href(replacement) {
+                        Some(href) => badge.push_str(&format!(
+                            " → <a class=\"anusrit\" href=\"{}\">{}</a>",
+                            escape(&href),
+                            mantra
+                        )),
+                        None => badge.push_str(&format!(" → <span class=\"anusrit undefined\">{}</span>", mantra)),
+                    }
+                }
+                if let Some(reason) = &note.reason {
+                    badge.push_str(&format!(" <span class=\"reason\">{}</span>", escape(reason)));
+                }
+                badge
+            }
+        };
+        Some(badge)
+    }
+}
+
+/// Markdown as html, with the quote blocks starting on the lines of
+/// `blocks` marked as bhasyas of a kind and followed by their panel
+fn markdown_html(text: &str, mut blocks: HashMap<usize, (&str, String)>) -> String {
+    let lines = LineIndex::new(text);
+    let mut open = Vec::new();
+    let events = Parser::new_ext(text, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH)
+        .into_offset_iter()
+        .map(|(event, range)| match event {
+            Event::Start(Tag::BlockQuote(_)) => match blocks.remove(&lines.line_of(range.start)) {
+                Some((class, panel)) => {
+                    open.push(Some(panel));
+                    Event::Html(CowStr::from(format!("<blockquote class=\"bhasya {}\">\n", class)))
+                }
+                None => {
+                    open.push(None);
+                    event
+                }
+            },
+            Event::End(TagEnd::BlockQuote(_)) => match open.pop().flatten() {
+                Some(panel) => Event::Html(CowStr::from(format!("</blockquote>\n{}", panel))),
+                None => event,
+            },
+            event => event,
+        });
+    let mut out = String::new();
+    html::push_html(&mut out, events);
+    out
+}
+
+/// Where an anusrit is in `content`, its `@shastra` suffix included, and
+/// its text as html
+fn anusrit_text(content: &str, anusrit: &Anusrit) -> (Range<usize>, String) {
+    let mut range = anusrit.span.bytes();
+    let mut text = escape(&anusrit.mantra_text);
+    if let Some(shastra) = &anusrit.shastra {
+        let suffix = content.get(range.end..).and_then(|rest| rest.strip_prefix('@'));
+        if suffix.is_some_and(|rest| rest.starts_with(shastra.as_str())) {
+            range.end += 1 + shastra.len();
+        }
+        text.push_str(&format!("<span class=\"shastra\">@{}</span>", escape(shastra)));
+    }
+    (range, text)
+}
+
+/// The `shastra:`, `khandita:` or `tyakta:` line right before a bhasya
+fn prefix_line(content: &str, bhasya: &Bhasya) -> Option<Range<usize>> {
+    let end = content.get(..bhasya.span.start.byte)?.strip_suffix('\n')?.len();
+    let start = content[..end].rfind('\n').map_or(0, |i| i + 1);
+    let line = content[start..end].trim_start();
+    ["shastra:", "khandita:", "tyakta:"].iter().any(|p| line.starts_with(p)).then_some(start..end)
+}
+
+/// The anusrits to the mantras a bhasya defines, as a panel below it
+// _| vyasa serve shows backlinks |_
+fn backlinks(repo: &Repository, bhasya: &Bhasya, base: &str) -> String {
+    let mut panel = String::new();
+    for mula in &bhasya.mulas {
+        let uses = repo.anusrits_to(&mula.mantra_text);
+        if uses.is_empty() {
+            continue;
+        }
+        panel.push_str(&format!(
+            "<details class=\"backlinks\"><summary>anusrits to {} ({})</summary>\n<ul>\n",
+            escape(&mula.mantra_text),
+            uses.len()
+        ));
+        for anusrit in uses {
+            panel.push_str(&format!(
+                "<li><a href=\"{}{}#a{}-{}\">{}:{}</a></li>\n",
+                base,
+                file_page(repo, &anusrit.file),
+                anusrit.span.start.line,
+                anusrit.span.start.column,
+                escape(&relative_to(repo, &anusrit.file)),
+                anusrit.line
+            ));
+        }
+        panel.push_str("</ul>\n</details>\n");
+    }
+    panel
+}
+
+fn kind_class(kind: &BhasyaKind) -> &'static str {
+    match kind {
+        BhasyaKind::Mula => "mula",
+        BhasyaKind::Uddhrit(_) => "uddhrit",
+        BhasyaKind::Khandita(_) => "khandita",
+        BhasyaKind::Tyakta(_) => "tyakta",
+    }
+}
+
+/// Whether every range lies in `content` on character boundaries - spans
+/// from a parse no longer fit a file changed since
+fn fits(content: &str, edits: &[(Range<usize>, String)]) -> bool {
+    edits.iter().all(|(range, _)| content.get(range.clone()).is_some())
+}
+
+/// `content` with each range replaced by its html, the text between passed through `text`
+fn splice(content: &str, edits: &[(Range<usize>, String)], text: impl Fn(&str) -> String) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut cursor = 0;
+    for (range, html) in edits {
+        if range.start < cursor {
+            continue;
+        }
+        out.push_str(&text(&content[cursor..range.start]));
+        out.push_str(html);
+        cursor = range.end;
+    }
+    out.push_str(&text(&content[cursor..]));
+    out
+}
+
+/// Line numbers of byte offsets
+struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    fn new(text: &str) -> Self {
+        let starts = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        LineIndex { starts }
+    }
+
+    /// 0-based line of a byte offset
+    fn line_of(&self, byte: usize) -> usize {
+        self.starts.partition_point(|&start| start <= byte) - 1
+    }
+}
+
+/// An id for a mantra, usable in a url fragment and a file name: letters and
+/// digits as they are, spaces as `-`, anything else as `_` and its utf-8
+/// bytes in hex - so different mantras never share one
+pub fn slug(mantra: &str) -> String {
+    let mut slug = String::with_capacity(mantra.len());
+    for c in mantra.chars() {
+        match c {
+            ' ' => slug.push('-'),
+            c if c.is_alphanumeric() => slug.push(c),
+            c => {
+                let mut bytes = [0; 4];
+                for byte in c.encode_utf8(&mut bytes).bytes() {
+                    slug.push_str(&format!("_{:02x}", byte));
+                }
+            }
+        }
+    }
+    slug
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// _| vyasa build has a searchable index |_
+/// Hides the mantras and files of the index that don't contain every word typed
+const SEARCH: &str = "<input id=\"search\" type=\"search\" placeholder=\"search mantras and files\" autofocus>
+<script>
+document.getElementById('search').addEventListener('input', event => {
+  const words = event.target.value.toLowerCase().split(/\\s+/).filter(Boolean);
+  for (const item of document.querySelectorAll('ul.mantras li, ul.files li'))
+    item.hidden = !words.every(word => item.textContent.toLowerCase().includes(word));
+});
+</script>";
+
+const STYLE: &str = "
+body { font: 16px/1.5 system-ui, sans-serif; max-width: 50rem; margin: 0 auto; padding: 1rem 2rem; color: #222; }
+nav { margin-bottom: 1rem; }
+a { color: #1a5fb4; }
+blockquote { margin: 1rem 0; padding: 0.25rem 1rem; border-left: 4px solid #ccc; background: #fafafa; }
+blockquote.bhasya.mula { border-left-color: #1a5fb4; }
+blockquote.bhasya.uddhrit { border-left-color: #26a269; background: #f3faf5; }
+blockquote.bhasya.khandita { border-left-color: #c64600; background: #fdf4ee; }
+blockquote.bhasya.tyakta { border-left-color: #77767b; color: #77767b; }
+blockquote.bhasya.tyakta a.mula, span.bhasya.tyakta a.mula { text-decoration: line-through; }
+span.bhasya.mula { background: #eef3fb; }
+span.bhasya.uddhrit { background: #f3faf5; }
+span.bhasya.khandita { background: #fdf4ee; }
+span.bhasya.tyakta { color: #77767b; }
+#search { width: 100%; font: inherit; padding: 0.25rem 0.5rem; }
+ul.uses .context { color: #555; }
+a.mula { font-weight: bold; color: inherit; text-decoration: none; }
+a.mula:target { background: #fff3b0; }
+a.anusrit { text-decoration: underline dotted; }
+.anusrit.undefined { color: #c01c28; text-decoration: underline wavy; }
+.shastra { font-size: 0.8em; color: #666; }
+.badge { font-size: 0.75em; padding: 0.1em 0.5em; border-radius: 0.75em; color: #fff; background: #666; }
+.badge.uddhrit { background: #26a269; }
+.badge.khandita { background: #c64600; }
+.badge.tyakta { background: #77767b; }
+.reason { color: #666; font-style: italic; }
+details.backlinks { font-size: 0.9em; margin: -0.5rem 0 1rem 1rem; color: #555; }
+pre.source { background: #fafafa; padding: 1rem; overflow-x: auto; }
+.where, .uses { font-size: 0.85em; color: #666; }
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::{anusrit_marker, mula_marker};
+    use crate::testing::TempShastra;
+
+    #[test]
+    fn slugs_keep_letters_and_tell_mantras_apart() {
+        assert_eq!(slug("vyasa check"), "vyasa-check");
+        assert_eq!(slug("café 2"), "café-2");
+        assert_eq!(slug("a/b"), "a_2fb");
+        assert_ne!(slug("a-b"), slug("a b"));
+        assert_ne!(slug("a_b"), slug("a_5fb"));
+    }
+
+    #[test]
+    fn pages_of_files_changed_since_the_parse_are_not_rendered() {
+        let shastra = TempShastra::new(
+            "stale",
+            &[
+                ("notes.md", &format!("> {} - the first mantra.\n", mula_marker("dharma"))),
+                ("code.rs", &format!("// uses {} here\n", anusrit_marker("dharma"))),
+            ],
+        );
+        let repo = shastra.parse();
+        let mut resolver = ShastraResolver::new(&repo);
+        let mut site = Site::new(&repo, &mut resolver);
+        assert!(site.render("files/code.rs.html").unwrap().contains("class=\"anusrit\""));
+
+        // the anusrit's span now starts inside a character, the bhasya's past the end
+        shastra.write("code.rs", "// धधधधधधधधधध\n");
+        shastra.write("notes.md", "x\n");
+        assert!(site.render("files/code.rs.html").is_none());
+        assert!(site.render("files/notes.md.html").is_none());
+        let page = site.render(&mantra_page("dharma")).unwrap();
+        assert!(!page.contains("धध"));
+    }
+}
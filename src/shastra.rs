@@ -0,0 +1,259 @@
+use crate::parser::{BhasyaKind, Repository, Span};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Resolves shastra aliases from `.vyasa/shastra.json` to parsed repositories
+///
+/// Each observed shastra is parsed at most once per resolver. Relative paths
+/// are resolved against the root of the observing shastra.
+pub struct ShastraResolver {
+    root: Option<PathBuf>,
+    aliases: HashMap<String, String>,
+    parsed: HashMap<String, Result<Repository, ResolveError>>,
+}
+
+/// Why an alias could not be resolved to a parsed shastra
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError {
+    /// The alias is not in shastra.json (or shastra.local.json)
+    UndefinedAlias(String),
+    /// The alias points at something other than a local folder path
+    NotAFolder { alias: String, path: String },
+    /// The folder does not exist
+    Missing { alias: String, path: String },
+    /// The folder exists but could not be parsed
+    ParseFailed { alias: String, path: String, error: String },
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::UndefinedAlias(alias) => write!(f, "undefined shastra '{}'", alias),
+            ResolveError::NotAFolder { alias, path } => write!(
+                f,
+                "shastra '{}' refers to '{}' - only local folder paths are currently supported",
+                alias, path
+            ),
+            ResolveError::Missing { alias, path } => {
+                write!(f, "shastra '{}' folder does not exist: {}", alias, path)
+            }
+            ResolveError::ParseFailed { alias, path, error } => {
+                write!(f, "failed to parse shastra '{}' at {}: {}", alias, path, error)
+            }
+        }
+    }
+}
+
+impl ShastraResolver {
+    /// Resolver for the shastras observed by `repo`
+    pub fn new(repo: &Repository) -> Self {
+        ShastraResolver {
+            root: repo.root.clone(),
+            aliases: repo.shastra_config.aliases.clone(),
+            parsed: HashMap::new(),
+        }
+    }
+
+    /// All aliases, sorted
+    pub fn aliases(&self) -> Vec<String> {
+        let mut aliases: Vec<_> = self.aliases.keys().cloned().collect();
+        aliases.sort();
+        aliases
+    }
+
+    /// The folder an alias points to, checked to be a local folder that exists
+    pub fn path(&self, alias: &str) -> Result<PathBuf, ResolveError> {
+        let Some(path) = self.aliases.get(alias) else {
+            return Err(ResolveError::UndefinedAlias(alias.to_string()));
+        };
+
+        if !is_folder_path(path) {
+            return Err(ResolveError::NotAFolder { alias: alias.to_string(), path: path.clone() });
+        }
+
+        let resolved = match &self.root {
+            Some(root) if !Path::new(path).is_absolute() => root.join(path),
+            _ => PathBuf::from(path),
+        };
+        if !resolved.exists() {
+            return Err(ResolveError::Missing { alias: alias.to_string(), path: path.clone() });
+        }
+        Ok(resolved)
+    }
+
+    /// Parse (once) and return the shastra an alias points to
+    pub fn resolve(&mut self, alias: &str) -> Result<&Repository, ResolveError> {
+        if !self.parsed.contains_key(alias) {
+            let result = self.path(alias).and_then(|path| {
+                Repository::parse_with_cache(&path, false).map_err(|error| ResolveError::ParseFailed {
+                    alias: alias.to_string(),
+                    path: path.to_string_lossy().to_string(),
+                    error,
+                })
+            });
+            self.parsed.insert(alias.to_string(), result);
+        }
+        self.parsed[alias].as_ref().map_err(|e| e.clone())
+    }
+
+    /// Drop the parsed shastras holding `file`, so they are parsed again
+    /// when next resolved - whether there was one
+    pub fn forget(&mut self, file: &Path) -> bool {
+        let Ok(file) = file.canonicalize() else {
+            return false;
+        };
+        let holding: Vec<String> = self
+            .parsed
+            .keys()
+            .filter(|alias| {
+                let folder = self.path(alias).ok().and_then(|path| path.canonicalize().ok());
+                folder.is_some_and(|folder| file.starts_with(folder))
+            })
+            .cloned()
+            .collect();
+        for alias in &holding {
+            self.parsed.remove(alias);
+        }
+        !holding.is_empty()
+    }
+}
+
+/// Whether `name` can be an alias - letters, digits, `-` and `_`, what an
+/// anusrit's `@shastra` suffix is read as
+pub fn is_alias(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Whether an alias target is a local folder path - the only kind supported
+pub fn is_folder_path(path: &str) -> bool {
+    path.starts_with('/') || path.starts_with("./") || path.starts_with("../")
+}
+
+/// Aliases in one shastra file (`shastra.json` or `shastra.local.json`),
+/// sorted so the file is rewritten in a stable order
+pub type Aliases = BTreeMap<String, String>;
+
+/// Read an alias file - empty if it does not exist, an error if it is not a
+/// JSON object of strings (so it is never silently overwritten)
+pub fn read_aliases(file: &Path) -> Result<Aliases, String> {
+    match fs::read_to_string(file) {
+        Ok(content) => serde_json::from_str(&content)
+            .map_err(|e| format!("{} is not a valid alias file: {}", file.display(), e)),
+        Err(_) => Ok(Aliases::new()),
+    }
+}
+
+/// Write an alias file as pretty JSON, sorted by alias
+pub fn write_aliases(file: &Path, aliases: &Aliases) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(aliases).map_err(|e| e.to_string())?;
+    fs::write(file, json + "\n").map_err(|e| format!("failed to write {}: {}", file.display(), e))
+}
+
+/// Check that `path` is a shastra - a folder with `.vyasa/config.json` naming
+/// it - and parse it
+pub fn open_shastra(path: &Path) -> Result<(String, Repository), String> {
+    if !path.join(".vyasa/config.json").is_file() {
+        return Err(format!("{} is not a shastra - it has no .vyasa/config.json", path.display()));
+    }
+    let repo = Repository::parse_with_cache(path, false)?;
+    match repo.config.name.clone() {
+        Some(name) => Ok((name, repo)),
+        None => Err(format!("{} has no 'name' in .vyasa/config.json", path.display())),
+    }
+}
+
+/// A use of one of our mantras in a shastra we observe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalUse {
+    /// Alias of the shastra, as we observe it
+    pub shastra: String,
+    pub file: String,
+    pub span: Span,
+    /// "anusrit" or "uddhrit"
+    pub kind: &'static str,
+}
+
+/// Anusrits and uddhrits of `mantra` from us, in the shastras we observe
+pub fn uses_in_observed(repo: &Repository, mantra: &str) -> Vec<ExternalUse> {
+    let mut uses = Vec::new();
+    let mut resolver = ShastraResolver::new(repo);
+    for alias in resolver.aliases() {
+        let Ok(external) = resolver.resolve(&alias) else {
+            continue;
+        };
+        // the observed shastra may know us by another alias
+        let refers_to_us = |name: &str| {
+            Some(name) == repo.config.name.as_deref() || same_shastra(external, name, repo)
+        };
+        let anusrits = external
+            .anusrits
+            .iter()
+            .chain(external.bhasyas.iter().flat_map(|b| &b.anusrits))
+            .chain(external.tyakta_replacements());
+        for anusrit in anusrits.filter(|a| a.mantra_text == mantra) {
+            if anusrit.shastra.as_deref().is_none_or(refers_to_us) {
+                uses.push(ExternalUse {
+                    shastra: alias.clone(),
+                    file: anusrit.file.clone(),
+                    span: anusrit.span,
+                    kind: "anusrit",
+                });
+            }
+        }
+        for (text, bhasya) in external.mula_mantras_with_bhasyas() {
+            if text == mantra && matches!(&bhasya.kind, BhasyaKind::Uddhrit(s) if refers_to_us(s)) {
+                uses.push(ExternalUse {
+                    shastra: alias.clone(),
+                    file: bhasya.file.clone(),
+                    span: bhasya.span,
+                    kind: "uddhrit",
+                });
+            }
+        }
+    }
+    uses
+}
+
+/// Whether `alias` in `external`'s shastra.json points at `repo`
+fn same_shastra(external: &Repository, alias: &str, repo: &Repository) -> bool {
+    let (Some(path), Some(external_root), Some(root)) =
+        (external.shastra_config.aliases.get(alias), &external.root, &repo.root)
+    else {
+        return false;
+    };
+    let target = external_root.join(path);
+    match (target.canonicalize(), Path::new(root).canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempShastra;
+
+    #[test]
+    fn aliases_are_what_an_anusrit_suffix_reads() {
+        assert!(is_alias("gita"));
+        assert!(is_alias("old-notes_2"));
+        assert!(is_alias("café"));
+        assert!(!is_alias(""));
+        assert!(!is_alias("my notes"));
+        assert!(!is_alias("a.b"));
+        assert!(!is_alias("a@b"));
+    }
+
+    #[test]
+    fn observed_shastras_are_parsed_without_writing_a_cache() {
+        let other = TempShastra::new("uncached", &[("notes.md", "text\n")]);
+        let shastra = TempShastra::new("caching", &[("notes.md", "text\n")]);
+        shastra.observe("other", &other);
+        let repo = shastra.parse();
+        assert!(ShastraResolver::new(&repo).resolve("other").is_ok());
+        assert!(shastra.path(".vyasa/cache").is_dir());
+        assert!(!other.path(".vyasa/cache").exists());
+    }
+}
@@ -0,0 +1,58 @@
+//! Throwaway shastras on disk for unit tests
+
+use crate::parser::Repository;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// A shastra in a fresh temporary folder, removed when dropped
+pub struct TempShastra {
+    pub root: PathBuf,
+}
+
+impl TempShastra {
+    /// A shastra named `name` holding `files`, given as (path from the root, content)
+    pub fn new(name: &str, files: &[(&str, &str)]) -> Self {
+        let id = NEXT.fetch_add(1, Ordering::SeqCst);
+        let root = std::env::temp_dir().join(format!("vyasa-test-{}-{}-{}", std::process::id(), name, id));
+        let _ = fs::remove_dir_all(&root);
+        let shastra = TempShastra { root };
+        shastra.write(".vyasa/config.json", &format!("{{\"name\": \"{}\"}}", name));
+        for (path, content) in files {
+            shastra.write(path, content);
+        }
+        shastra
+    }
+
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    pub fn write(&self, relative: &str, content: &str) {
+        let path = self.path(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    pub fn read(&self, relative: &str) -> String {
+        fs::read_to_string(self.path(relative)).unwrap()
+    }
+
+    /// Observe `other` under `alias`, by its absolute path
+    pub fn observe(&self, alias: &str, other: &TempShastra) {
+        let aliases = serde_json::json!({ alias: other.root.to_string_lossy() });
+        self.write(".vyasa/shastra.json", &aliases.to_string());
+    }
+
+    pub fn parse(&self) -> Repository {
+        Repository::parse(&self.root).unwrap()
+    }
+}
+
+impl Drop for TempShastra {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
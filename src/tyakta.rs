@@ -0,0 +1,142 @@
+use crate::edit::{anusrit_marker, Changes, QuoteAffixes};
+use crate::parser::{normalize_mantra, Anusrit, BhasyaKind, Repository};
+use crate::shastra::{uses_in_observed, ExternalUse};
+use chrono::{Local, NaiveDate};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+/// The edits that deprecate a mantra, and what stops resolving once they land
+#[derive(Debug, Default)]
+pub struct TyaktaPlan<'a> {
+    pub mantra: String,
+    /// The mantra to use instead, named on the `tyakta:` line
+    pub replacement: Option<String>,
+    pub changes: Changes,
+    /// Mula bhasyas marked tyakta
+    pub bhasyas: usize,
+    /// Other mantras defined in those bhasyas - they are deprecated too
+    pub also_deprecated: Vec<String>,
+    /// Anusrits in this shastra that become undefined
+    pub broken_anusrits: Vec<&'a Anusrit>,
+    /// Anusrits and uddhrits in observed shastras that break
+    pub downstream: Vec<ExternalUse>,
+}
+
+/// Plan marking every mula bhasya of `mantra` with a `tyakta:` line dated
+/// today, in the comment style of the line it lives on. `replacement` must be
+/// another live mantra of this shastra.
+pub fn plan<'a>(repo: &'a Repository, mantra: &str, replacement: Option<&str>) -> Result<TyaktaPlan<'a>, String> {
+    let mantra = normalize_mantra(mantra);
+    if !repo.is_live(&mantra) {
+        return Err(if repo.is_tyakta(&mantra) {
+            format!("^{}^ is already tyakta", mantra)
+        } else {
+            format!("no live mantra ^{}^ in this shastra", mantra)
+        });
+    }
+    let replacement = replacement.map(normalize_mantra);
+    if let Some(replacement) = &replacement {
+        if *replacement == mantra {
+            return Err("a mantra can't replace itself".to_string());
+        }
+        if !repo.is_live(replacement) {
+            return Err(format!("replacement ^{}^ is not a live mantra in this shastra", replacement));
+        }
+    }
+
+    let line = tyakta_line(Some(Local::now().date_naive()), replacement.as_deref());
+    let mut plan = TyaktaPlan { mantra: mantra.clone(), replacement, ..TyaktaPlan::default() };
+    let mut also_deprecated = BTreeSet::new();
+    for bhasya in repo.bhasyas_for_mantra(&mantra) {
+        let defines = bhasya.mulas.iter().any(|m| m.mantra_text == mantra);
+        if !defines || !matches!(bhasya.kind, BhasyaKind::Mula) {
+            continue;
+        }
+        let content = fs::read_to_string(&bhasya.file).map_err(|e| format!("failed to read {}: {}", bhasya.file, e))?;
+        let syntax = repo.config.comment_syntax(Path::new(&bhasya.file));
+        plan.changes.push(QuoteAffixes::insert_before(&bhasya.file, &content, bhasya.span.start.byte, &syntax, &line));
+        plan.bhasyas += 1;
+        also_deprecated.extend(bhasya.mulas.iter().map(|m| m.mantra_text.clone()).filter(|m| *m != mantra));
+    }
+
+    plan.also_deprecated = also_deprecated.into_iter().collect();
+    plan.broken_anusrits = repo.anusrits_to(&mantra);
+    plan.downstream = uses_in_observed(repo, &mantra);
+    Ok(plan)
+}
+
+/// `tyakta: 2024-05-01 -> _| replacement |_`, leaving out what is not given
+pub fn tyakta_line(date: Option<NaiveDate>, replacement: Option<&str>) -> String {
+    let mut line = "tyakta:".to_string();
+    if let Some(date) = date {
+        line.push_str(&format!(" {}", date.format("%Y-%m-%d")));
+    }
+    if let Some(replacement) = replacement {
+        line.push_str(&format!(" -> {}", anusrit_marker(replacement)));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::mula_marker;
+    use crate::testing::TempShastra;
+
+    #[test]
+    fn marks_each_mula_bhasya_and_lists_what_breaks() {
+        let notes = format!(
+            "> {} - defined together\n> {} - with this one.\n\n> {} - the successor.\n",
+            mula_marker("old"),
+            mula_marker("sibling"),
+            mula_marker("new")
+        );
+        let code = format!("// uses {}\n", anusrit_marker("old"));
+        let shastra = TempShastra::new("deprecate", &[("notes.md", &notes), ("code.rs", &code)]);
+        let repo = shastra.parse();
+        let plan = plan(&repo, "old", Some("new")).unwrap();
+        assert_eq!(plan.bhasyas, 1);
+        assert_eq!(plan.also_deprecated, vec!["sibling".to_string()]);
+        assert_eq!(plan.broken_anusrits.len(), 1);
+        plan.changes.write().unwrap();
+
+        let line = tyakta_line(Some(Local::now().date_naive()), Some("new"));
+        assert_eq!(shastra.read("notes.md"), format!("{}\n{}", line, notes));
+        let repo = shastra.parse();
+        assert!(repo.is_tyakta("old") && repo.is_tyakta("sibling"));
+        assert_eq!(repo.replacement_for("old").map(|a| a.mantra_text.as_str()), Some("new"));
+    }
+
+    #[test]
+    fn replacement_must_be_another_live_mantra() {
+        let notes = format!("tyakta:\n> {} - gone.\n\n> {} - here.\n", mula_marker("gone"), mula_marker("here"));
+        let shastra = TempShastra::new("deprecate", &[("notes.md", &notes)]);
+        let repo = shastra.parse();
+        assert!(plan(&repo, "here", Some("here")).unwrap_err().contains("itself"));
+        assert!(plan(&repo, "here", Some("gone")).unwrap_err().contains("not a live mantra"));
+        assert!(plan(&repo, "gone", None).unwrap_err().contains("already tyakta"));
+        assert!(plan(&repo, "nowhere", None).unwrap_err().contains("no live mantra"));
+    }
+
+    #[test]
+    fn tyakta_line_leaves_out_what_is_not_given() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1);
+        assert_eq!(tyakta_line(None, None), "tyakta:");
+        assert_eq!(tyakta_line(date, None), "tyakta: 2024-05-01");
+        assert_eq!(tyakta_line(date, Some("new")), format!("tyakta: 2024-05-01 -> {}", anusrit_marker("new")));
+    }
+
+    #[test]
+    fn tyakta_line_opens_a_block_comment_the_quote_continues() {
+        let code = format!("/* > {} - one\n * > two\n */\nfn f() {{}}\n", mula_marker("old"));
+        let shastra = TempShastra::new("blocks", &[("code.rs", &code)]);
+        let repo = shastra.parse();
+        plan(&repo, "old", None).unwrap().changes.write().unwrap();
+
+        let line = tyakta_line(Some(Local::now().date_naive()), None);
+        let expected = format!("/* {}\n * > {} - one\n * > two\n */\nfn f() {{}}\n", line, mula_marker("old"));
+        assert_eq!(shastra.read("code.rs"), expected);
+        assert!(matches!(shastra.parse().bhasyas[0].kind, BhasyaKind::Tyakta(_)));
+    }
+}
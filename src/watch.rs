@@ -0,0 +1,214 @@
+use crate::check::{check_with, Diagnostic};
+use crate::parser::{parse_file, Repository};
+use crate::render::Site;
+use crate::shastra::ShastraResolver;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A shastra kept parsed in memory between file changes
+pub struct Watch {
+    /// The path as given - file names in the repository start with it
+    path: PathBuf,
+    /// The same path, absolute, as file events name it
+    absolute: PathBuf,
+    repo: Repository,
+    /// Observed shastras, parsed once and again only when the config changes
+    resolver: ShastraResolver,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// What changed on disk and what it did to the check results
+#[derive(Debug, Default)]
+pub struct Update {
+    /// Files parsed again, as the repository names them
+    pub files: Vec<String>,
+    /// Whether a `.vyasa` config file changed, so everything was parsed again
+    pub reloaded: bool,
+    /// Violations that were not there before
+    pub new: Vec<Diagnostic>,
+    /// Violations that are gone
+    pub resolved: Vec<Diagnostic>,
+}
+
+impl Watch {
+    /// Parse and check the shastra at `path`
+    pub fn new(path: &Path) -> Result<Self, String> {
+        let absolute = path.canonicalize().map_err(|e| format!("can't watch {}: {}", path.display(), e))?;
+        let repo = Repository::parse(path)?;
+        let mut resolver = ShastraResolver::new(&repo);
+        let diagnostics = check_with(&repo, &mut resolver);
+        Ok(Watch { path: path.to_path_buf(), absolute, repo, resolver, diagnostics })
+    }
+
+    /// Folder to watch, recursively
+    pub fn folder(&self) -> &Path {
+        &self.absolute
+    }
+
+    /// The shastra as of the last update
+    pub fn repo(&self) -> &Repository {
+        &self.repo
+    }
+
+    /// The shastra as of the last update, as a site to render
+    pub fn site(&mut self) -> Site<'_> {
+        Site::new(&self.repo, &mut self.resolver)
+    }
+
+    /// Violations as of the last update
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    // _| vyasa watch reparses only changed files |_
+    /// Parse the changed files again - the whole shastra if its config
+    /// changed - check it and compare with the last check. Paths vyasa
+    /// doesn't scan are ignored; an update with no files means nothing to do.
+    pub fn update(&mut self, changed: &[PathBuf]) -> Result<Update, String> {
+        let mut update = Update::default();
+        for path in changed {
+            let Ok(relative) = path.strip_prefix(&self.absolute) else {
+                continue;
+            };
+            if is_config(relative) {
+                update.reloaded = true;
+                continue;
+            }
+            if path.is_dir() {
+                continue;
+            }
+            let file = self.path.join(relative).to_string_lossy().to_string();
+            if self.repo.scans(Path::new(&file)) && !update.files.contains(&file) {
+                update.files.push(file);
+            }
+        }
+
+        if update.reloaded {
+            self.repo = Repository::parse(&self.path)?;
+            self.resolver = ShastraResolver::new(&self.repo);
+        } else if update.files.is_empty() {
+            return Ok(update);
+        } else {
+            for file in &update.files {
+                let syntax = self.repo.config.comment_syntax(Path::new(file));
+                // a deleted file parses to nothing
+                let parsed = fs::read_to_string(file).map(|text| parse_file(&text, file, &syntax)).unwrap_or_default();
+                self.repo.replace_file(file, parsed);
+            }
+        }
+
+        let diagnostics = check_with(&self.repo, &mut self.resolver);
+        (update.new, update.resolved) = compare(&self.diagnostics, &diagnostics);
+        self.diagnostics = diagnostics;
+        Ok(update)
+    }
+}
+
+/// `.vyasa/config.json` and the shastra files beside it (not the cache below it)
+fn is_config(relative: &Path) -> bool {
+    relative.parent() == Some(Path::new(".vyasa")) && relative.extension().is_some_and(|e| e == "json")
+}
+
+/// Violations in `after` but not `before`, and in `before` but not `after`.
+/// Where a violation is doesn't count, only what and in which file - an edit
+/// above it moves it without making it new.
+pub fn compare(before: &[Diagnostic], after: &[Diagnostic]) -> (Vec<Diagnostic>, Vec<Diagnostic>) {
+    let key = |d: &Diagnostic| (d.rule, d.file.clone(), d.message.clone(), d.notes.clone());
+    let count = |diagnostics: &[Diagnostic]| {
+        let mut counts = HashMap::new();
+        for diagnostic in diagnostics {
+            *counts.entry(key(diagnostic)).or_insert(0) += 1;
+        }
+        counts
+    };
+    let (mut seen_before, mut seen_after) = (count(before), count(after));
+    let mut new = Vec::new();
+    for diagnostic in after {
+        match seen_before.get_mut(&key(diagnostic)) {
+            Some(n) if *n > 0 => *n -= 1,
+            _ => new.push(diagnostic.clone()),
+        }
+    }
+    let mut resolved = Vec::new();
+    for diagnostic in before {
+        match seen_after.get_mut(&key(diagnostic)) {
+            Some(n) if *n > 0 => *n -= 1,
+            _ => resolved.push(diagnostic.clone()),
+        }
+    }
+    (new, resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::check::Rule;
+    use crate::edit::{anusrit_marker, mula_marker};
+    use crate::parser::{Position, Span};
+    use crate::testing::TempShastra;
+
+    /// An undefined anusrit to `mantra` on `line` of `file`
+    fn undefined(file: &str, line: usize, mantra: &str) -> Diagnostic {
+        let at = Position { line, column: 1, byte: 0 };
+        Diagnostic {
+            rule: Rule::UndefinedAnusrit,
+            file: Some(file.to_string()),
+            span: Some(Span { start: at, end: at }),
+            message: format!("^{}^", mantra),
+            notes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn moved_violations_are_neither_new_nor_resolved() {
+        let before = [undefined("a.md", 1, "x"), undefined("a.md", 5, "y")];
+        let after = [undefined("a.md", 2, "x"), undefined("b.md", 5, "y"), undefined("a.md", 9, "z")];
+        let (new, resolved) = compare(&before, &after);
+        assert_eq!(new, vec![after[1].clone(), after[2].clone()]);
+        assert_eq!(resolved, vec![before[1].clone()]);
+    }
+
+    #[test]
+    fn repeated_violations_are_counted() {
+        let one = [undefined("a.md", 1, "x")];
+        let two = [undefined("a.md", 1, "x"), undefined("a.md", 3, "x")];
+        assert_eq!(compare(&one, &two).0.len(), 1);
+        assert_eq!(compare(&two, &one).1.len(), 1);
+        assert_eq!(compare(&two, &two), (Vec::new(), Vec::new()));
+    }
+
+    #[test]
+    fn files_ignored_below_the_top_are_not_parsed_again() {
+        let shastra = TempShastra::new("watched", &[("src/.gitignore", "generated.rs\n")]);
+        let mut watch = Watch::new(&shastra.root).unwrap();
+        let line = format!("// {}\n", anusrit_marker("nowhere"));
+        shastra.write("src/generated.rs", &line);
+        shastra.write("src/lib.rs", &line);
+        let folder = watch.folder().to_path_buf();
+        let update = watch.update(&[folder.join("src/generated.rs"), folder.join("src/lib.rs")]).unwrap();
+        assert_eq!(update.files, vec![shastra.path("src/lib.rs").to_string_lossy().to_string()]);
+        assert_eq!(update.new.len(), 1);
+    }
+
+    #[test]
+    fn observed_shastras_are_parsed_again_only_when_the_config_changes() {
+        let other = TempShastra::new("watched-other", &[("theirs.md", "nothing yet\n")]);
+        let shastra = TempShastra::new("watching", &[]);
+        shastra.observe("other", &other);
+        shastra.write("use.md", &format!("the {}\n", anusrit_marker("karma")));
+        let mut watch = Watch::new(&shastra.root).unwrap();
+        assert_eq!(watch.diagnostics().len(), 1);
+
+        other.write("theirs.md", &format!("> {} - action.\n", mula_marker("karma")));
+        shastra.write("use.md", &format!("the {} again\n", anusrit_marker("karma")));
+        let folder = watch.folder().to_path_buf();
+        let update = watch.update(&[folder.join("use.md")]).unwrap();
+        assert_eq!(update.files.len(), 1);
+        assert_eq!(watch.diagnostics().len(), 1);
+
+        let update = watch.update(&[folder.join(".vyasa/shastra.json")]).unwrap();
+        assert!(update.reloaded);
+        assert!(watch.diagnostics().is_empty());
+    }
+}